@@ -7,6 +7,8 @@ extern crate test;
 extern crate hyphenation as hyphenation_crate;
 extern crate unicode_wrap;
 
+use std::sync::Arc;
+
 use hyphenation_crate::Language;
 
 use unicode_wrap::*;
@@ -38,7 +40,7 @@ fn lorem_ipsum(length: usize) -> &'static str {
 fn run( size: usize, b: &mut Bencher, hyphenate: bool )
 {
     let c    = hyphenation_crate::load( Language::Latin ).unwrap();
-    let hyph = Box::new( Hyphenator{ priority: 0, corpus: c, glue: "-\n".to_string() } );
+    let hyph = Box::new( Hyphenator{ priority: 0, corpus: Arc::new( c ), glue: "-\n".to_string(), quality: Default::default() } );
     let xi   = Box::new( Xi{ priority: 0 } );
     let text = lorem_ipsum( size );
 
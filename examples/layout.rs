@@ -5,11 +5,7 @@ extern crate unicode_wrap;
 
 use hyphenation::Language;
 
-use unicode_wrap::Wrapper;
-
-use unicode_wrap :: generator :: unicode_standard :: Xi           ;
-use unicode_wrap :: generator :: hyphenation      :: Hyphenator   ;
-use unicode_wrap :: ruler     :: unicode_width    :: UnicodeWidth ;
+use unicode_wrap::Builder;
 
 fn main()
 {
@@ -21,19 +17,16 @@ Zero-cost abstractions.\
 
 	let mut prev_lines = vec![];
 
-	let c       = hyphenation::load( Language::English_US ).unwrap();
-	let hyph    = Box::new( Hyphenator{ priority: 0, corpus: c, glue: "-\n".to_string() } );
-
-	let xi      = Box::new( Xi{ priority: 0 } );
+	let wrapper  = Builder::new( 15 ).hyphenation( Language::English_US ).build().unwrap();
 
-	let mut wrapper = Wrapper::new( 15, vec![ hyph, xi ], vec![], UnicodeWidth, false ).unwrap();
+	// Run the generators only once for all widths.
+	//
+	let prepared = wrapper.prepare( example );
 
 
 	for width in 15..60
 	{
-		wrapper.set_width( width ).expect( "need non-zero width" );
-
-		if let Ok( s ) = wrapper.wrap( example )
+		if let Ok( s ) = wrapper.wrap_prepared( &prepared, width )
 		{
 			let lines: Vec<_> = s.lines().map( |slice| slice.to_string() ).collect();
 
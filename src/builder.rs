@@ -0,0 +1,250 @@
+use std::sync::Arc;
+
+use hyphenation_crate::{ self, Language };
+
+use super::*;
+
+use generator::unicode_standard::Xi           ;
+use generator::hyphenation     ::{ Hyphenator, Quality };
+use ruler::unicode_width       ::UnicodeWidth ;
+
+
+// Sets up a Wrapper one option at a time. Without generators, we use Xi, also when hyphenating unless default_generator
+// is turned off, and without a ruler, UnicodeWidth.
+//
+//     let wrapper = Builder::new( 72 ).hyphenation( Language::English_US ).break_word( true ).build()?;
+//
+// The presets give the common configurations, and can be changed further before calling build.
+//
+pub struct Builder< Ruler >
+{
+	width      : usize                     ,
+	generators : Vec< Box<Generate> >      ,
+	filters    : Vec< Box<Filter>   >      ,
+	ruler      : Ruler                     ,
+	break_word : bool                      ,
+	glue       : String                    ,
+	ladder     : Option<( String, usize )> ,
+	last_line  : Option< LastLine >        ,
+	balanced   : bool                      ,
+	hyphenation: Option< Language >        , // Loaded when we build, see Builder::hyphenation.
+	default_gen: bool                      , // Whether to use Xi when no generators were added.
+}
+
+
+
+impl Builder< UnicodeWidth >
+{
+	pub fn new( width: usize ) -> Builder< UnicodeWidth >
+	{
+		Builder
+		{
+			width      : width            ,
+			generators : Vec::new()       ,
+			filters    : Vec::new()       ,
+			ruler      : UnicodeWidth     ,
+			break_word : false            ,
+			glue       : "\n".to_string() ,
+			ladder     : None             ,
+			last_line  : None             ,
+			balanced   : false            ,
+			hyphenation: None             ,
+			default_gen: true             ,
+		}
+	}
+
+
+	// For terminals: break on whitespace and the like, and break words that don't fit rather than fail.
+	//
+	pub fn terminal( width: usize ) -> Builder< UnicodeWidth >
+	{
+		Builder::new( width ).generator( Xi{ priority: 0 } ).break_word( true )
+	}
+
+
+	// English prose: hyphenate, but not more than two lines in a row, keep "Dr. Smith" and "10 km" together and don't leave
+	// a single word on the last line of a paragraph.
+	//
+	pub fn english( width: usize ) -> Builder< UnicodeWidth >
+	{
+		Builder::new( width )
+
+			.hyphenation( Language::English_US                 )
+			.generator  ( Xi{ priority: 0 }                    )
+			.filter     ( filter::binding::Binding::english()  )
+			.ladder     ( Some(( "hyphenation".to_string(), 2 )) )
+			.last_line  ( Some( LastLine::Words( 2 ) )         )
+			.break_word ( true                                 )
+	}
+
+
+	// French prose: like english, with the French rules for punctuation and narrow no-break spaces.
+	//
+	pub fn french( width: usize ) -> Builder< UnicodeWidth >
+	{
+		Builder::new( width )
+
+			.hyphenation( Language::French                                  )
+			.generator  ( Xi{ priority: 0 }                                 )
+			.filter     ( filter::french::French{ narrow_nbsp: true }       )
+			.ladder     ( Some(( "hyphenation".to_string(), 2 ))            )
+			.last_line  ( Some( LastLine::Words( 2 ) )                      )
+			.break_word ( true                                              )
+	}
+
+
+	// Comments in source code: no hyphenation, and never break inside `code` or urls, so they can still be copied.
+	// Words are broken rather than failing on long identifiers.
+	//
+	pub fn comments( width: usize ) -> Builder< UnicodeWidth >
+	{
+		Builder::new( width )
+
+			.generator ( Xi{ priority: 0 }                                                    )
+			.filter    ( filter::pattern::Pattern::new( r"`[^`\n]*`"        ).unwrap()        )
+			.filter    ( filter::pattern::Pattern::new( r"\b\w+://[^\s]+"   ).unwrap()        )
+			.break_word( true                                                                 )
+	}
+}
+
+
+
+impl< Ruler > Builder< Ruler > where Ruler: TextWidth
+{
+	pub fn width( mut self, width: usize ) -> Self { self.width = width; self }
+
+
+	pub fn generator< G >( mut self, generator: G ) -> Self where G: Generate + 'static
+	{
+		self.generators.push( Box::new( generator ) );
+		self
+	}
+
+
+	pub fn filter< F >( mut self, filter: F ) -> Self where F: Filter + 'static
+	{
+		self.filters.push( Box::new( filter ) );
+		self
+	}
+
+
+	// Add generators and filters that are already boxed, eg. when they are chosen at runtime.
+	//
+	pub fn generators( mut self, generators: Vec< Box<Generate> > ) -> Self { self.generators.extend( generators ); self }
+	pub fn filters   ( mut self, filters   : Vec< Box<Filter>   > ) -> Self { self.filters   .extend( filters    ); self }
+
+
+	pub fn ruler< R >( self, ruler: R ) -> Builder< R > where R: TextWidth
+	{
+		Builder
+		{
+			width      : self.width       ,
+			generators : self.generators  ,
+			filters    : self.filters     ,
+			ruler      : ruler            ,
+			break_word : self.break_word  ,
+			glue       : self.glue        ,
+			ladder     : self.ladder      ,
+			last_line  : self.last_line   ,
+			balanced   : self.balanced    ,
+			hyphenation: self.hyphenation ,
+			default_gen: self.default_gen ,
+		}
+	}
+
+
+	pub fn break_word( mut self, break_word: bool                      ) -> Self { self.break_word = break_word        ; self }
+	pub fn glue      ( mut self, glue      : &str                      ) -> Self { self.glue       = glue.to_string()  ; self }
+	pub fn ladder    ( mut self, ladder    : Option<( String, usize )> ) -> Self { self.ladder     = ladder            ; self }
+	pub fn last_line ( mut self, last_line : Option< LastLine >        ) -> Self { self.last_line  = last_line         ; self }
+	pub fn balanced  ( mut self, balanced  : bool                      ) -> Self { self.balanced   = balanced          ; self }
+
+
+	// Whether to use Xi when no generators were added. Turn it off to only hyphenate.
+	//
+	pub fn default_generator( mut self, default_generator: bool ) -> Self { self.default_gen = default_generator; self }
+
+
+	// Hyphenate with the dictionary for language, which is loaded when we build. The Hyphenator comes before the other
+	// generators, with priority 0 and the default Quality.
+	//
+	pub fn hyphenation( mut self, language: Language ) -> Self
+	{
+		self.hyphenation = Some( language );
+		self
+	}
+
+
+	pub fn build( self ) -> Result< Wrapper< Ruler >, &'static str >
+	{
+		let mut generators = self.generators;
+
+		if generators.is_empty()  &&  self.default_gen { generators.push( Box::new( Xi{ priority: 0 } ) ) }
+
+		if let Some( language ) = self.hyphenation
+		{
+			let corpus = hyphenation_crate::load( language ).map_err( |_| "Could not load the hyphenation dictionary" )?;
+
+			generators.insert( 0, Box::new( Hyphenator{ priority: 0, corpus: Arc::new( corpus ), glue: "-\n".to_string(), quality: Quality::default() } ) );
+		}
+
+
+		let mut wrapper = Wrapper::new( self.width, generators, self.filters, self.ruler, self.break_word )?;
+
+		wrapper.set_glue     ( &self.glue      );
+		wrapper.set_ladder   ( self.ladder     );
+		wrapper.set_last_line( self.last_line  );
+		wrapper.set_balanced ( self.balanced   );
+
+		Ok( wrapper )
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+
+	#[test]
+	fn defaults()
+	{
+		let wrapper = Builder::new( 5 ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "foo bar baz" ), Ok( "foo\nbar\nbaz".to_string() ) );
+		assert_eq!( wrapper.wrap( "foobarbaz"   ), Err( "No valid split point found" ) );
+	}
+
+
+	#[test]
+	fn zero_width()
+	{
+		assert_eq!( Builder::new( 0 ).build().err(), Some( "Wrapper.width cannot be zero" ) );
+	}
+
+
+	#[test]
+	fn options()
+	{
+		let wrapper = Builder::new( 5 ).hyphenation( Language::English_US ).glue( "\\\n" ).break_word( true ).balanced( true ).build().unwrap();
+
+		assert_eq!( wrapper.glue()    , "\\\n" );
+		assert_eq!( wrapper.balanced(), true   );
+		assert_eq!( wrapper.wrap( "hyphenation xxxxxx" ), Ok( "hy-\nphen-\nation\nxxxxx\\\nx".to_string() ) );
+	}
+
+
+	#[test]
+	fn presets()
+	{
+		assert_eq!( Builder::terminal( 3 ).build().unwrap().wrap( "abcdef" ), Ok( "abc\ndef".to_string() ) );
+
+		assert_eq!( Builder::english( 10 ).build().unwrap().wrap( "see Dr. Smith" ), Ok( "see\nDr. Smith".to_string() ) );
+
+		assert_eq!( Builder::french( 10 ).build().unwrap().wrap( "oui ! non" ), Ok( "oui\u{202F}! non".to_string() ) );
+
+		assert_eq!( Builder::comments( 10 ).build().unwrap().wrap( "use `a b c d` now" ), Ok( "use\n`a b c d`\nnow".to_string() ) );
+	}
+}
@@ -0,0 +1,567 @@
+use std::collections::{ BTreeMap, HashMap };
+use std::sync::Arc;
+
+use hyphenation_crate::Language;
+use serde::{ Serialize, Deserialize };
+
+use super::*;
+
+use generator::unicode_standard::Xi                    ;
+use generator::hyphenation     ::{ Hyphenator, Quality };
+use ruler::unicode_width       ::UnicodeWidth          ;
+
+
+// The configuration of a Wrapper as data, so it can be read from a config file, eg. in TOML:
+//
+//     width       = 72
+//     hyphenation = "en-us"
+//     break_word  = true
+//
+//     [[generators]]
+//     name     = "xi"
+//     priority = 1
+//
+//     [[filters]]
+//     name        = "french"
+//     narrow_nbsp = true
+//
+// Generators and filters are looked up by name in a Registry when we build. Everything is optional and defaults to what
+// Builder does, with a width of 80. Misspelled keys are an error rather than ignored, like unknown options of components.
+//
+#[ derive( Serialize, Deserialize, PartialEq, Eq, Clone, Debug ) ]
+#[ serde( default, deny_unknown_fields ) ]
+//
+pub struct Config
+{
+	pub width      : usize              ,
+	pub generators : Vec< Component >   ,
+	pub filters    : Vec< Component >   ,
+	pub ruler      : String             , // Only "unicode_width" for now.
+	pub break_word : bool               ,
+	pub glue       : String             , // The glue for break_word.
+	pub ladder     : Option< Ladder >   ,
+	pub last_line  : Option< LastLine > ,
+	pub balanced   : bool               ,
+	pub hyphenation: Option< String >   , // A language code for language, like "en-us". Adds a Hyphenator before the generators.
+}
+
+
+
+impl Default for Config
+{
+	fn default() -> Config
+	{
+		Config
+		{
+			width      : 80                      ,
+			generators : Vec::new()              ,
+			filters    : Vec::new()              ,
+			ruler      : "unicode_width".into()  ,
+			break_word : false                   ,
+			glue       : "\n".into()             ,
+			ladder     : None                    ,
+			last_line  : None                    ,
+			balanced   : false                   ,
+			hyphenation: None                    ,
+		}
+	}
+}
+
+
+
+impl Config
+{
+	pub fn build( &self, registry: &Registry ) -> Result< Wrapper< UnicodeWidth >, &'static str >
+	{
+		if self.ruler != "unicode_width" { return Err( "Unknown ruler" ) }
+
+		let generators = self.generators.iter().map( |c| registry.generator( c ) ).collect::< Result< Vec<_>, _ > >()?;
+		let filters    = self.filters   .iter().map( |c| registry.filter   ( c ) ).collect::< Result< Vec<_>, _ > >()?;
+
+		let mut builder = Builder::new( self.width )
+
+			.generators( generators                                                    )
+			.filters   ( filters                                                       )
+			.break_word( self.break_word                                               )
+			.glue      ( &self.glue                                                    )
+			.ladder    ( self.ladder.as_ref().map( |l| ( l.generator.clone(), l.max ) ) )
+			.last_line ( self.last_line                                                )
+			.balanced  ( self.balanced                                                 )
+		;
+
+		if let Some( ref code ) = self.hyphenation
+		{
+			builder = builder.hyphenation( language( code )? );
+		}
+
+		builder.build()
+	}
+}
+
+
+
+// See Wrapper::set_ladder.
+//
+#[ derive( Serialize, Deserialize, PartialEq, Eq, Clone, Debug ) ]
+#[ serde( deny_unknown_fields ) ]
+//
+pub struct Ladder
+{
+	pub generator: String , // The name of the generator, eg. "hyphenation".
+	pub max      : usize  ,
+}
+
+
+
+// A generator or filter by name, with its options, eg. { name = "xi", priority = 1 }. What options there are depends on the
+// constructor in the Registry.
+//
+#[ derive( Serialize, Deserialize, PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct Component
+{
+	pub name   : String                    ,
+
+	#[ serde( flatten ) ]
+	pub options: BTreeMap< String, Value > ,
+}
+
+
+
+#[ derive( Serialize, Deserialize, PartialEq, Eq, Clone, Debug ) ]
+#[ serde( untagged ) ]
+//
+pub enum Value
+{
+	Bool   ( bool         ),
+	Integer( i64          ),
+	String ( String       ),
+	List   ( Vec< Value > ),
+}
+
+
+
+impl Component
+{
+	pub fn new( name: &str ) -> Component
+	{
+		Component{ name: name.to_string(), options: BTreeMap::new() }
+	}
+
+
+	// Fails if there are options other than keys, so that a misspelled option doesn't go unnoticed. Constructors in a
+	// Registry call this before reading the options.
+	//
+	pub fn only( &self, keys: &[&str] ) -> Result< (), &'static str >
+	{
+		match self.options.keys().all( |k| keys.contains( &k.as_str() ) )
+		{
+			true  => Ok( () ),
+			false => Err( "Unknown option" ),
+		}
+	}
+
+
+	pub fn bool( &self, key: &str, default: bool ) -> Result< bool, &'static str >
+	{
+		match self.options.get( key )
+		{
+			None                      => Ok( default ),
+			Some( &Value::Bool( b ) ) => Ok( b       ),
+			_                         => Err( "Option has the wrong type" ),
+		}
+	}
+
+
+	pub fn usize( &self, key: &str, default: usize ) -> Result< usize, &'static str >
+	{
+		match self.options.get( key )
+		{
+			None                                   => Ok( default    ),
+			Some( &Value::Integer( i ) ) if i >= 0 => Ok( i as usize ),
+			_                                      => Err( "Option has the wrong type" ),
+		}
+	}
+
+
+	pub fn string( &self, key: &str ) -> Result< Option< &str >, &'static str >
+	{
+		match self.options.get( key )
+		{
+			None                            => Ok( None      ),
+			Some( &Value::String( ref s ) ) => Ok( Some( s ) ),
+			_                               => Err( "Option has the wrong type" ),
+		}
+	}
+
+
+	pub fn strings( &self, key: &str ) -> Result< Option< Vec< &str > >, &'static str >
+	{
+		match self.options.get( key )
+		{
+			None                            => Ok( None ),
+
+			Some( &Value::List( ref list ) ) => list.iter().map( |v| match *v
+			{
+				Value::String( ref s ) => Ok( s.as_str() ),
+				_                      => Err( "Option has the wrong type" ),
+
+			}).collect::< Result< Vec<_>, _ > >().map( Some ),
+
+			_                               => Err( "Option has the wrong type" ),
+		}
+	}
+}
+
+
+
+pub type MakeGenerator = Box< Fn( &Component ) -> Result< Box<Generate>, &'static str > >;
+pub type MakeFilter    = Box< Fn( &Component ) -> Result< Box<Filter>  , &'static str > >;
+
+
+// Constructors for generators and filters by name. Registry::default knows the ones in this crate:
+//
+// generators:
+//
+// - xi         : priority
+// - hyphenation: language (required), priority, glue ("-\n"), left_min, right_min, capitalised, acronyms, digits, last_word
+// - pattern    : regex (required), priority, glue
+//
+// filters:
+//
+// - french       : narrow_nbsp
+// - binding      : abbreviations, units (the English ones if left out)
+// - single_letter: language ("cs", "sk" or "pl") or words, penalty
+// - pattern      : regex (required)
+//
+// Options other than these fail with "Unknown option". Add your own with add_generator and add_filter, and use
+// Component::only to check their options the same way. A name that is added again replaces the earlier constructor.
+//
+pub struct Registry
+{
+	generators: HashMap< String, MakeGenerator > ,
+	filters   : HashMap< String, MakeFilter    > ,
+}
+
+
+
+impl Registry
+{
+	// A registry that knows nothing, see Registry::default for the built in generators and filters.
+	//
+	pub fn new() -> Registry
+	{
+		Registry{ generators: HashMap::new(), filters: HashMap::new() }
+	}
+
+
+	pub fn add_generator< F >( &mut self, name: &str, make: F )
+
+		where F: Fn( &Component ) -> Result< Box<Generate>, &'static str > + 'static
+	{
+		self.generators.insert( name.to_string(), Box::new( make ) );
+	}
+
+
+	pub fn add_filter< F >( &mut self, name: &str, make: F )
+
+		where F: Fn( &Component ) -> Result< Box<Filter>, &'static str > + 'static
+	{
+		self.filters.insert( name.to_string(), Box::new( make ) );
+	}
+
+
+	pub fn generator( &self, component: &Component ) -> Result< Box<Generate>, &'static str >
+	{
+		match self.generators.get( &component.name )
+		{
+			Some( make ) => make( component ),
+			None         => Err( "Unknown generator" ),
+		}
+	}
+
+
+	pub fn filter( &self, component: &Component ) -> Result< Box<Filter>, &'static str >
+	{
+		match self.filters.get( &component.name )
+		{
+			Some( make ) => make( component ),
+			None         => Err( "Unknown filter" ),
+		}
+	}
+}
+
+
+
+impl Default for Registry
+{
+	fn default() -> Registry
+	{
+		let mut registry = Registry::new();
+
+		registry.add_generator( "xi"           , xi                 );
+		registry.add_generator( "hyphenation"  , hyphenator         );
+		registry.add_generator( "pattern"      , pattern_generator  );
+
+		registry.add_filter   ( "french"       , french             );
+		registry.add_filter   ( "binding"      , binding            );
+		registry.add_filter   ( "single_letter", single_letter      );
+		registry.add_filter   ( "pattern"      , pattern_filter     );
+
+		registry
+	}
+}
+
+
+
+// The languages of the hyphenation dictionaries, by their code as in "en-us".
+//
+pub fn language( code: &str ) -> Result< Language, &'static str >
+{
+	match code.to_lowercase().as_str()
+	{
+		"en-us"   => Ok( Language::English_US  ),
+		"en-gb"   => Ok( Language::English_GB  ),
+		"fr"      => Ok( Language::French      ),
+		"de-1996" => Ok( Language::German_1996 ),
+		"es"      => Ok( Language::Spanish     ),
+		"it"      => Ok( Language::Italian     ),
+		"cs"      => Ok( Language::Czech       ),
+		"sk"      => Ok( Language::Slovak      ),
+		"pl"      => Ok( Language::Polish      ),
+		"la"      => Ok( Language::Latin       ),
+		_         => Err( "Unknown language" ),
+	}
+}
+
+
+
+fn xi( c: &Component ) -> Result< Box<Generate>, &'static str >
+{
+	c.only( &[ "priority" ] )?;
+
+	Ok( Box::new( Xi{ priority: c.usize( "priority", 0 )? } ) )
+}
+
+
+fn hyphenator( c: &Component ) -> Result< Box<Generate>, &'static str >
+{
+	c.only( &[ "language", "priority", "glue", "left_min", "right_min", "capitalised", "acronyms", "digits", "last_word" ] )?;
+
+	let code   = c.string( "language" )?.ok_or( "Missing option" )?;
+	let corpus = hyphenation_crate::load( language( code )? ).map_err( |_| "Could not load the hyphenation dictionary" )?;
+
+	let d = Quality::default();
+
+	let quality = Quality
+	{
+		left_min   : c.usize( "left_min"   , d.left_min    )? ,
+		right_min  : c.usize( "right_min"  , d.right_min   )? ,
+		capitalised: c.bool ( "capitalised", d.capitalised )? ,
+		acronyms   : c.bool ( "acronyms"   , d.acronyms    )? ,
+		digits     : c.bool ( "digits"     , d.digits      )? ,
+		last_word  : c.bool ( "last_word"  , d.last_word   )? ,
+	};
+
+	Ok( Box::new( Hyphenator
+	{
+		priority: c.usize( "priority", 0 )?                          ,
+		corpus  : Arc::new( corpus )                                 ,
+		glue    : c.string( "glue" )?.unwrap_or( "-\n" ).to_string() ,
+		quality : quality                                            ,
+	}))
+}
+
+
+fn pattern_generator( c: &Component ) -> Result< Box<Generate>, &'static str >
+{
+	c.only( &[ "regex", "priority", "glue" ] )?;
+
+	let mut pattern = generator::pattern::Pattern::new( c.string( "regex" )?.ok_or( "Missing option" )? )?;
+
+	pattern.priority = c.usize( "priority", pattern.priority )?;
+
+	if let Some( glue ) = c.string( "glue" )? { pattern.glue = glue.to_string() }
+
+	Ok( Box::new( pattern ) )
+}
+
+
+fn french( c: &Component ) -> Result< Box<Filter>, &'static str >
+{
+	c.only( &[ "narrow_nbsp" ] )?;
+
+	Ok( Box::new( filter::french::French{ narrow_nbsp: c.bool( "narrow_nbsp", false )? } ) )
+}
+
+
+fn binding( c: &Component ) -> Result< Box<Filter>, &'static str >
+{
+	use filter::binding::{ Binding, ENGLISH_ABBREVIATIONS, UNITS };
+
+	c.only( &[ "abbreviations", "units" ] )?;
+
+	let abbreviations = c.strings( "abbreviations" )?.unwrap_or_else( || ENGLISH_ABBREVIATIONS.to_vec() );
+	let units         = c.strings( "units"         )?.unwrap_or_else( || UNITS                .to_vec() );
+
+	Ok( Box::new( Binding::new( &abbreviations, &units ) ) )
+}
+
+
+fn single_letter( c: &Component ) -> Result< Box<Filter>, &'static str >
+{
+	use filter::single_letter::SingleLetter;
+
+	c.only( &[ "language", "words", "penalty" ] )?;
+
+	let mut filter = match ( c.string( "language" )?, c.strings( "words" )? )
+	{
+		( _           , Some( words ) ) => SingleLetter::new( &words )         ,
+		( Some( "cs" ), None          ) => SingleLetter::czech ()              ,
+		( Some( "sk" ), None          ) => SingleLetter::slovak()              ,
+		( Some( "pl" ), None          ) => SingleLetter::polish()              ,
+		( Some( _    ), None          ) => return Err( "Unknown language" )    ,
+		( None        , None          ) => return Err( "Missing option"   )    ,
+	};
+
+	if c.options.contains_key( "penalty" ) { filter.penalty = Some( c.usize( "penalty", 0 )? ) }
+
+	Ok( Box::new( filter ) )
+}
+
+
+fn pattern_filter( c: &Component ) -> Result< Box<Filter>, &'static str >
+{
+	c.only( &[ "regex" ] )?;
+
+	Ok( Box::new( filter::pattern::Pattern::new( c.string( "regex" )?.ok_or( "Missing option" )? )? ) )
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	extern crate serde_json;
+	extern crate toml;
+
+	use super::*;
+
+
+	#[test]
+	fn from_toml()
+	{
+		let config: Config = toml::from_str( r#"
+
+			width       = 10
+			hyphenation = "en-us"
+			break_word  = true
+			last_line   = { words = 1 }
+
+			[[generators]]
+			name     = "xi"
+			priority = 1
+
+			[[filters]]
+			name        = "french"
+			narrow_nbsp = true
+
+		"# ).unwrap();
+
+		assert_eq!( config.generators[ 0 ].usize( "priority", 0 ), Ok( 1 )                         );
+		assert_eq!( config.last_line                             , Some( LastLine::Words( 1 ) )    );
+		assert_eq!( config.glue                                  , "\n"                            );
+
+		let wrapper = config.build( &Registry::default() ).unwrap();
+
+		assert_eq!( wrapper.wrap( "the hyphenation « a »" ), Ok( "the hy-\nphenation\n«\u{202F}a\u{202F}»".to_string() ) );
+	}
+
+
+	#[test]
+	fn from_json()
+	{
+		let config: Config = serde_json::from_str( r#"
+		{
+			"width"     : 4,
+			"generators": [ { "name": "xi" }, { "name": "pattern", "regex": "->", "priority": 1 } ],
+			"filters"   : [ { "name": "single_letter", "words": [ "x" ] } ],
+			"ladder"    : { "generator": "hyphenation", "max": 3 }
+		}
+		"# ).unwrap();
+
+		assert_eq!( config.ladder, Some( Ladder{ generator: "hyphenation".to_string(), max: 3 } ) );
+
+		let wrapper = config.build( &Registry::default() ).unwrap();
+
+		assert_eq!( wrapper.wrap( "v x yy"    ), Ok( "v\nx yy"     .to_string() ) );
+		assert_eq!( wrapper.wrap( "ab->cd->e" ), Ok( "ab->\ncd->\ne".to_string() ) );
+	}
+
+
+	#[test]
+	fn round_trip()
+	{
+		let mut config = Config::default();
+
+		config.generators.push( Component::new( "xi" ) );
+		config.generators[ 0 ].options.insert( "priority".to_string(), Value::Integer( 2 ) );
+		config.last_line = Some( LastLine::Width( 4 ) );
+
+		let json = serde_json::to_string( &config ).unwrap();
+
+		assert_eq!( serde_json::from_str::< Config >( &json ).unwrap(), config );
+	}
+
+
+	#[test]
+	fn errors()
+	{
+		let registry = Registry::default();
+		let build    = |json: &str| serde_json::from_str::< Config >( json ).unwrap().build( &registry ).err();
+
+		assert_eq!( build( r#"{ "generators": [ { "name": "foo" } ] }"#                          ), Some( "Unknown generator"         ) );
+		assert_eq!( build( r#"{ "filters"   : [ { "name": "foo" } ] }"#                          ), Some( "Unknown filter"            ) );
+		assert_eq!( build( r#"{ "ruler"     : "pixels" }"#                                       ), Some( "Unknown ruler"             ) );
+		assert_eq!( build( r#"{ "hyphenation": "xx" }"#                                          ), Some( "Unknown language"          ) );
+		assert_eq!( build( r#"{ "generators": [ { "name": "pattern" } ] }"#                      ), Some( "Missing option"            ) );
+		assert_eq!( build( r#"{ "generators": [ { "name": "xi", "priority": "high" } ] }"#       ), Some( "Option has the wrong type" ) );
+		assert_eq!( build( r#"{ "generators": [ { "name": "xi", "priorty": 1 } ] }"#            ), Some( "Unknown option"            ) );
+		assert_eq!( build( r#"{ "filters"   : [ { "name": "french", "narrow": true } ] }"#       ), Some( "Unknown option"            ) );
+		assert_eq!( build( r#"{ "width": 0 }"#                                                   ), Some( "Wrapper.width cannot be zero" ) );
+	}
+
+
+	#[test]
+	fn unknown_fields()
+	{
+		assert!( serde_json::from_str::< Config >( r#"{ "break_words": true }"#                                 ).is_err() );
+		assert!( serde_json::from_str::< Config >( r#"{ "ladder": { "generatr": "hyphenation", "max": 2 } }"# ).is_err() );
+
+		assert!( toml::from_str::< Config >( "break_words = true"                              ).is_err() );
+		assert!( toml::from_str::< Config >( "ladder = { generator = \"xi\", mx = 2 }"       ).is_err() );
+		assert!( toml::from_str::< Config >( "ladder = { generator = \"xi\", max = 2 }"      ).is_ok () );
+	}
+
+
+	#[test]
+	fn custom()
+	{
+		let mut registry = Registry::new();
+
+		registry.add_generator( "dashes", |c: &Component|
+		{
+			let mut pattern = generator::pattern::Pattern::new( "-" )?;
+			pattern.priority = c.usize( "priority", 0 )?;
+
+			Ok( Box::new( pattern ) as Box<Generate> )
+		});
+
+		let mut config = Config::default();
+		config.width   = 3;
+		config.generators.push( Component::new( "dashes" ) );
+
+		assert_eq!( config.build( &registry ).unwrap().wrap( "ab-cd" ), Ok( "ab-\ncd".to_string() ) );
+		assert_eq!( config.build( &Registry::new() ).err(), Some( "Unknown generator" ) );
+	}
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+
+use super::*;
+
+
+// Why the wrapper didn't cut a line at some split point. See Wrapper::explain.
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub enum Reason
+{
+	Disabled ( Option< String > )                        , // By the last filter that changed it, or None if it came disabled from the generator.
+	Mandatory                                            , // A mandatory break comes first.
+	Outscored{ score: isize, winner: isize }             , // See SplitPoint::score.
+	Ladder                                               , // It scores better, but too many lines in a row already ended in a split from its generator.
+	LastLine                                             , // It scores better, but the last line of the paragraph would come out too short.
+	NotReached                                           , // It scores better, but choosing the cut stopped looking before it, see Wrapper::find.
+	Balanced ( WidthOffset )                             , // It fits the width of the wrapper, but is this much over the narrower width balancing chose.
+	Overflow ( WidthOffset )                             , // The line would be this much wider than the wrapper allows.
+}
+
+
+
+// A line of the wrapped text, the split we cut it at and the splits we could have cut it at instead.
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct Explained
+{
+	pub line    : Line                        ,
+	pub cut     : Option< SplitPoint >        , // None for the last line.
+	pub rejected: Vec<( SplitPoint, Reason )> , // Sorted on where they start.
+}
+
+
+
+// Why the text was wrapped the way it was, line by line.
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct Explanation
+{
+	pub width: usize            , // The width the lines were wrapped to, narrower than that of the wrapper when balancing.
+	pub lines: Vec< Explained > ,
+}
+
+
+
+impl fmt::Display for Reason
+{
+	fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result
+	{
+		match *self
+		{
+			Reason::Disabled( Some( ref name ) ) if name.is_empty() => write!( f, "disabled by a filter"                          ),
+			Reason::Disabled( Some( ref name ) )                    => write!( f, "disabled by {}", name                          ),
+			Reason::Disabled( None )                                => write!( f, "disabled by its generator"                     ),
+			Reason::Mandatory                                       => write!( f, "a mandatory break comes first"                 ),
+			Reason::Outscored{ score, winner }                      => write!( f, "score {} is lower than {}", score, winner      ),
+			Reason::Ladder                                          => write!( f, "avoided for the ladder"                        ),
+			Reason::LastLine                                        => write!( f, "avoided for the last line"                     ),
+			Reason::NotReached                                      => write!( f, "not reached"                                   ),
+			Reason::Balanced( by )                                  => write!( f, "over the balanced width by {}", by.0           ),
+			Reason::Overflow( by )                                  => write!( f, "over the width by {}", by.0                    ),
+		}
+	}
+}
+
+
+
+impl fmt::Display for Explanation
+{
+	fn fmt( &self, f: &mut fmt::Formatter ) -> fmt::Result
+	{
+		for ( i, explained ) in self.lines.iter().enumerate()
+		{
+			write!( f, "{:>3} {:?}", i + 1, explained.line.text )?;
+
+			match explained.cut
+			{
+				Some( ref cut ) => writeln!( f, " cut at {}-{}", cut.start.0, cut.end.0 )?,
+				None            => writeln!( f, ""                                      )?,
+			}
+
+			for &( ref split, ref reason ) in &explained.rejected
+			{
+				writeln!( f, "    {}-{}: {}", split.start.0, split.end.0, reason )?;
+			}
+		}
+
+		Ok(())
+	}
+}
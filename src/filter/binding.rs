@@ -0,0 +1,192 @@
+pub use super::*;
+
+use self::Filter;
+
+// Abbreviations after which we don't break in English text. Ones that are also words that often end a sentence, like "etc.",
+// "no." or "art.", are left out on purpose, since not breaking after every sentence that ends in them is worse.
+//
+pub const ENGLISH_ABBREVIATIONS: &'static [&'static str] = &
+[
+	"Dr."  , "Mr." , "Mrs.", "Ms." , "Prof.", "St.", "Jr.", "Sr.", "Mt.",
+	"e.g." , "i.e.", "cf." , "vs." , "approx.",
+	"E.g." , "I.e.", "Cf." ,
+	"p."   , "pp." , "Fig.", "Vol." , "ch.",
+];
+
+// Units and symbols that stay on the same line as the number next to them, both "10 km" and "€ 50".
+// Short units that are also common English words (in, t, l) are left out, and so are single capitals (A, B, V, W), which
+// start sentences more often than they follow numbers.
+//
+pub const UNITS: &'static [&'static str] = &
+[
+	"%"  , "‰"  , "°"  , "°C" , "°F" ,
+	"mm" , "cm" , "m"  , "km" , "ft" , "mi" , "px" , "pt" , "em" ,
+	"mg" , "g"  , "kg" , "lb" , "oz" ,
+	"ml" , "cl" , "dl" ,
+	"ms" , "s"  , "min", "h"  ,
+	"Hz" , "kHz", "MHz", "GHz",
+	"mV" , "kV" , "kW" , "kWh", "mA" ,
+	"kB" , "KB" , "MB" , "GB" , "TB" , "KiB", "MiB", "GiB", "TiB",
+	"€"  , "$"  , "£"  , "¥"  ,
+];
+
+// Trailing punctuation that doesn't change the meaning of a unit or number after a split, as in "5 GB, 10 GB" or "€ 50.".
+//
+const TRAILING: &'static [char] = &[ ',', '.', ';', ':', '!', '?', ')', ']' ];
+
+
+// Disables splits after abbreviations ("Dr. Smith", "e.g. this", "p. 12") and between a number and a unit or symbol in either
+// order ("10 km", "€ 50", "25 %").
+//
+// Abbreviations and units are compared case sensitively, so "no. We" isn't taken for "No. 5" and "mm" isn't taken for "Mm".
+// Trailing punctuation is only allowed on the word after the split. On the word before it, it usually ends a sentence, as in
+// "Chapter 5. A new".
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct Binding
+{
+	pub abbreviations: Vec< String > ,
+	pub units        : Vec< String > ,
+}
+
+
+
+impl Binding
+{
+	pub fn new( abbreviations: &[&str], units: &[&str] ) -> Binding
+	{
+		Binding
+		{
+			abbreviations: abbreviations.iter().map( |a| a.to_string() ).collect() ,
+			units        : units        .iter().map( |u| u.to_string() ).collect() ,
+		}
+	}
+
+
+	pub fn english() -> Binding { Binding::new( ENGLISH_ABBREVIATIONS, UNITS ) }
+
+
+	fn is_unit( &self, word: &str ) -> bool
+	{
+		self.units.iter().any( |u| u == word )
+	}
+}
+
+
+
+impl Default for Binding
+{
+	fn default() -> Binding { Binding::english() }
+}
+
+
+
+impl Filter for Binding
+{
+	fn name( &self ) -> &str { "binding" }
+
+
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		for split in splits.iter_mut()
+		{
+			if split.mandatory { continue }
+
+			let before = util::word_before( text, split.start.0 );
+			let after  = util::word_after ( text, split.end  .0 );
+
+			if before.is_empty() || after.is_empty() { continue }
+
+
+			let abbreviation = before.trim_left_matches( '(' );
+			let after        = after .trim_right_matches( TRAILING );
+
+			if self.abbreviations.iter().any( |a| a == abbreviation )
+
+			|| ( is_number( before ) && self.is_unit( after  ) )
+			|| ( is_number( after  ) && self.is_unit( before ) )
+			{
+				split.enabled = false;
+			}
+		}
+	}
+}
+
+
+
+// Digits with separators, like "10", "1,000.5" or "-3".
+//
+fn is_number( word: &str ) -> bool
+{
+	word.chars().any( |c| c.is_numeric() )
+
+	&& word.chars().all( |c| c.is_numeric() || c == '.' || c == ',' || c == '\'' || c == '-' || c == '+' )
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use generator::unicode_standard::Xi;
+
+	fn filter( text: &str ) -> Vec< bool >
+	{
+		let mut opp = Xi{ priority: 0 }.opportunities( &text );
+
+		Binding::default().run( &text, &mut opp );
+
+		opp.iter().filter( |s| !s.mandatory ).map( |s| s.enabled ).collect()
+	}
+
+
+	#[test]
+	fn abbreviations()
+	{
+		assert_eq!( filter( "ask Dr. Smith" ), vec![ true , false ] );
+		assert_eq!( filter( "e.g. this"     ), vec![ false        ] );
+		assert_eq!( filter( "see p. 12"     ), vec![ true , false ] );
+		assert_eq!( filter( "and etc. Then" ), vec![ true , true  ] );
+	}
+
+
+	#[test]
+	fn sentence_ends()
+	{
+		assert_eq!( filter( "was no. We"    ), vec![ true , true  ] );
+		assert_eq!( filter( "the art. We"   ), vec![ true , true  ] );
+		assert_eq!( filter( "or Ch. Then"   ), vec![ true , true  ] );
+		assert_eq!( filter( "the VS. We"    ), vec![ true , true  ] );
+		assert_eq!( filter( "Chapter 5. A"  ), vec![ true , true  ] );
+		assert_eq!( filter( "is 5 GB. 10"   ), vec![ true , false, true ] );
+		assert_eq!( filter( "grade 5 B"     ), vec![ true , true  ] );
+		assert_eq!( filter( "plan B 5"      ), vec![ true , true  ] );
+		assert_eq!( filter( "page 5 V"      ), vec![ true , true  ] );
+	}
+
+
+	#[test]
+	fn units()
+	{
+		assert_eq!( filter( "5 GB left"     ), vec![ false, true  ] );
+		assert_eq!( filter( "only € 50"     ), vec![ true , false ] );
+		assert_eq!( filter( "at 10 km."     ), vec![ true , false ] );
+		assert_eq!( filter( "5 in the box"  ), vec![ true , true , true ] );
+	}
+
+
+	#[test]
+	fn custom()
+	{
+		let mut opp = Xi{ priority: 0 }.opportunities( "Art. 5 mph art. 6" );
+
+		Binding::new( &[ "Art." ], &[ "mph" ] ).run( "Art. 5 mph art. 6", &mut opp );
+
+		assert!( !opp[ 0 ].enabled );
+		assert!( !opp[ 1 ].enabled );
+		assert!(  opp[ 2 ].enabled );
+		assert!(  opp[ 3 ].enabled );
+	}
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+pub use super::*;
+
+use self::Filter;
+
+
+// Building blocks to make filters out of others.
+
+
+// Enables only the splits the filter would disable, and disables the others, eg. to allow breaks only inside the matches of
+// a filter::pattern::Pattern. Mandatory splits and splits that were disabled already are left alone.
+//
+// The filter runs on a copy of the splits, which are matched back by position and glue, so it may remove and reorder them.
+// A split it removes counts as disabled, splits it adds are ignored.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Invert< F >
+{
+	pub filter: F
+}
+
+
+
+impl< F > Filter for Invert< F > where F: Filter
+{
+	fn name ( &self ) -> &str { "invert"            }
+	fn local( &self ) -> bool { self.filter.local() }
+
+
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		let mut filtered = splits.clone();
+
+		self.filter.run( text, &mut filtered );
+
+		let enabled: HashMap< _, bool > = filtered.iter().map( |s| ( key( s ), s.enabled ) ).collect();
+
+		for split in splits.iter_mut()
+		{
+			if split.mandatory  ||  !split.enabled { continue }
+
+			split.enabled = !enabled.get( &key( split ) ).cloned().unwrap_or( false );
+		}
+	}
+}
+
+
+
+// Runs the filter on the splits of one generator only, by name, see Generate::name. The results are matched back by position
+// and glue, so the filter may change, remove, add and reorder splits like any other. Splits it adds go after the others.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Only< F >
+{
+	pub filter   : F      ,
+	pub generator: String ,
+}
+
+
+
+impl< F > Filter for Only< F > where F: Filter
+{
+	fn name ( &self ) -> &str { "only"              }
+	fn local( &self ) -> bool { self.filter.local() }
+
+
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		let mut ours: Vec< SplitPoint > = splits.iter().filter( |s| s.generator == self.generator ).cloned().collect();
+
+		self.filter.run( text, &mut ours );
+
+
+		// Where to find the results for each split, by position and glue. Several splits can share them, so keep a list
+		// and hand them out in order.
+		//
+		let mut results: Vec< Option<SplitPoint> > = ours.into_iter().map( Some ).collect();
+		let mut found  : HashMap< _, Vec<usize> > = HashMap::new();
+
+		for ( i, split ) in results.iter().enumerate().rev()
+		{
+			found.entry( key( split.as_ref().unwrap() ) ).or_insert_with( Vec::new ).push( i );
+		}
+
+
+		// Keep the others and our splits the filter kept where they were, leave out the ones it removed, then add the new ones.
+		//
+		let mut merged = Vec::with_capacity( splits.len() );
+
+		for split in splits.drain( .. )
+		{
+			if split.generator != self.generator { merged.push( split ); continue }
+
+			if let Some( i ) = found.get_mut( &key( &split ) ).and_then( |f| f.pop() )
+			{
+				merged.push( results[ i ].take().unwrap() );
+			}
+		}
+
+		merged.extend( results.into_iter().filter_map( |s| s ) );
+
+		*splits = merged;
+	}
+}
+
+
+
+// Runs filters one after the other, as one filter.
+//
+pub struct Chain
+{
+	pub filters: Vec< Box<Filter> >
+}
+
+
+
+impl Filter for Chain
+{
+	fn name( &self ) -> &str { "chain" }
+
+
+	fn local( &self ) -> bool
+	{
+		self.filters.iter().all( |f| f.local() )
+	}
+
+
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		for filter in &self.filters
+		{
+			filter.run( text, splits );
+		}
+	}
+}
+
+
+
+// Identifies a split when matching up the splits before and after a filter, like the wrapper does.
+//
+fn key( split: &SplitPoint ) -> ( ByteOffset, ByteOffset, String )
+{
+	( split.start, split.end, split.glue.clone() )
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use generator::unicode_standard::Xi;
+	use filter::pattern::Pattern;
+	use filter::french::French;
+
+	fn enabled( splits: &[SplitPoint] ) -> Vec< bool >
+	{
+		splits.iter().filter( |s| !s.mandatory ).map( |s| s.enabled ).collect()
+	}
+
+
+	#[test]
+	fn invert()
+	{
+		let mut splits = Xi{ priority: 0 }.opportunities( "a {{b c}} d" );
+
+		Invert{ filter: Pattern::new( r"\{\{.*?\}\}" ).unwrap() }.run( "a {{b c}} d", &mut splits );
+
+		assert_eq!( enabled( &splits ), vec![ false, true, false ] );
+
+
+		// The pattern didn't disable them, the inversion did.
+		//
+		let wrapper  = Builder::new( 5 ).filter( Invert{ filter: Pattern::new( r"\{\{.*?\}\}" ).unwrap() } ).build().unwrap();
+		let prepared = wrapper.prepare( "a {{b c}} d" );
+
+		assert_eq!( prepared.splits()[ 0 ].filters, vec![ "invert".to_string() ] );
+	}
+
+
+	#[test]
+	fn only()
+	{
+		let text       = "a % b c";
+		let mut splits = Xi{ priority: 0 }.opportunities( text );
+
+		for split in &mut splits { split.generator = "xi".to_string() }
+
+		Only{ filter: French::default(), generator: "other".to_string() }.run( text, &mut splits );
+
+		assert_eq!( enabled( &splits ), vec![ true, true, true ] );
+
+		Only{ filter: French::default(), generator: "xi".to_string() }.run( text, &mut splits );
+
+		assert_eq!( enabled( &splits ), vec![ false, true, true ] );
+	}
+
+
+	// What the inner filter does to the splits shouldn't move its results onto other splits.
+	//
+	#[test]
+	fn invert_remove_add_reorder()
+	{
+		let text    = "a b c d";
+		let shuffle = |_: &str, splits: &mut Vec<SplitPoint>|
+		{
+			splits.retain( |s| s.start.0 != 1 );
+			splits.reverse();
+			splits.push( SplitPoint::new( 0, 0, 0 ) );
+
+			for split in splits.iter_mut() { if split.start.0 == 5 { split.enabled = false } }
+		};
+
+		let mut splits = Xi{ priority: 0 }.opportunities( text );
+
+		Invert{ filter: shuffle }.run( text, &mut splits );
+
+		assert_eq!( splits.len(), 4 );
+		assert_eq!( enabled( &splits ), vec![ true, false, true ] );
+	}
+
+
+	#[test]
+	fn only_remove_add_reorder()
+	{
+		let text    = "a b c d e";
+		let shuffle = |_: &str, splits: &mut Vec<SplitPoint>|
+		{
+			splits.retain( |s| s.start.0 != 1 );
+			splits.reverse();
+			splits.push( SplitPoint::new( 0, 0, 0 ) );
+
+			for split in splits.iter_mut() { if split.start.0 == 5 { split.enabled = false } }
+		};
+
+		let mut splits = Xi{ priority: 0 }.opportunities( text );
+
+		for split in &mut splits { split.generator = if split.start.0 == 3 { "other" } else { "xi" }.to_string() }
+
+		Only{ filter: shuffle, generator: "xi".to_string() }.run( text, &mut splits );
+
+		let starts: Vec< ( usize, bool ) > = splits.iter().map( |s| ( s.start.0, s.enabled ) ).collect();
+
+		assert_eq!( starts, vec![ ( 3, true ), ( 5, false ), ( 7, true ), ( 9, true ), ( 0, true ) ] );
+	}
+
+
+	#[test]
+	fn chain()
+	{
+		let text       = "« a {{b c}} »";
+		let mut splits = Xi{ priority: 0 }.opportunities( text );
+
+		let chain = Chain{ filters: vec![ Box::new( French::default() ), Box::new( Pattern::new( r"\{\{.*?\}\}" ).unwrap() ) ] };
+
+		chain.run( text, &mut splits );
+
+		assert_eq!( enabled( &splits ), vec![ false, true, false, false ] );
+	}
+}
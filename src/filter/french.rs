@@ -2,47 +2,192 @@ pub use super::*;
 
 use self::Filter;
 
+// Characters that should not be separated from the word before them, even if there is a space in between.
+//
 const NO_BREAK_BEFORE: &'static [char] = &
 [
-	'»'
+	'»' ,
+	':' ,
+	';' ,
+	'!' ,
+	'?' ,
+	'%' ,
 ];
 
+// Characters that should not be separated from the word after them, even if there is a space in between.
+//
 const NO_BREAK_AFTER: &'static [char] = &
 [
 	'«'
 ];
 
+const EM_DASH          : char = '\u{2014}';
+const NARROW_NO_BREAK  : char = '\u{202F}';
+const NO_BREAK         : char = '\u{00A0}';
 
-#[ derive( PartialEq, Eq, Clone, Debug ) ]
+
+// French typography:
+//
+// - no break between a word and the high punctuation after it (: ; ! ? %) nor before a closing guillemet,
+// - no break between an opening guillemet and the word after it,
+// - no break after an em-dash that starts a dialogue or opens an incise, nor before the em-dash that closes an incise.
 //
-pub struct French;
+// When narrow_nbsp is set, breaking spaces before high punctuation and inside guillemets, like a space, a tab or a thin space,
+// are replaced by U+202F NARROW NO-BREAK SPACE in the output of the wrapper.
+//
+#[ derive( PartialEq, Eq, Clone, Debug, Default ) ]
+//
+pub struct French
+{
+	pub narrow_nbsp: bool
+}
+
+
 
 impl Filter for French
 {
+	fn name( &self ) -> &str { "french" }
+
+
 	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
 	{
-		for split in splits
+		let ( opening, closing ) = em_dashes( text );
+
+		for split in splits.iter_mut()
 		{
-			let c = text[ split.end.0.. ].chars().next();
+			// Never disable newlines and the like.
+			//
+			if split.mandatory { continue }
+
+
+			// Look past the whitespace range of the split, since generators might not consume all of it.
+			//
+			let c = text[ split.end.0.. ].chars().skip_while( |c| is_space( *c ) ).next();
+
+			if let Some( c ) = c
+			{
+				if NO_BREAK_BEFORE.contains( &c ) { split.enabled = false }
+			}
+
+
+			let d = text[ ..split.start.0 ].chars().rev().skip_while( |c| is_space( *c ) ).next();
 
-			if c.is_some() && NO_BREAK_BEFORE.contains( &c.unwrap() )
+			if let Some( d ) = d
 			{
-				split.enabled = false
+				if NO_BREAK_AFTER.contains( &d ) { split.enabled = false }
 			}
 
 
-			let d = text[ ..split.start.0 ].chars().next_back();
+			let next = split.end.0   + text[ split.end.0.. ].chars().take_while( |c| is_space( *c ) ).map( |c| c.len_utf8() ).sum::<usize>();
+			let prev = split.start.0 - text[ ..split.start.0 ].chars().rev().take_while( |c| is_space( *c ) ).map( |c| c.len_utf8() ).sum::<usize>();
+
+			if closing.contains( &next ) { split.enabled = false }
+
+			if prev >= EM_DASH.len_utf8()  &&  opening.contains( &( prev - EM_DASH.len_utf8() ) ) { split.enabled = false }
+		}
+
+
+		if self.narrow_nbsp { narrow( text, splits ) }
+	}
+}
+
+
+
+// Whitespace that can appear between a word and punctuation on one line.
+//
+fn is_space( c: char ) -> bool
+{
+	util::char_is_whitespace( &c )  ||  c == NO_BREAK  ||  c == NARROW_NO_BREAK
+}
+
+
+
+// Returns the byte offsets of the opening and the closing em-dashes in the text.
+// An em-dash at the start of a paragraph is a dialogue marker. The others alternate between opening and closing an incise
+// within a paragraph. Paragraphs end at any line break, like they do for Xi and Wrapper::edit.
+//
+fn em_dashes( text: &str ) -> ( Vec<usize>, Vec<usize> )
+{
+	let mut opening = Vec::new();
+	let mut closing = Vec::new();
+
+	let mut paragraph_start = true ;
+	let mut open            = false;
+
+	for ( i, c ) in text.char_indices()
+	{
+		if util::char_is_line_break( &c )
+		{
+			paragraph_start = true ;
+			open            = false;
+		}
+
+		else if c == EM_DASH
+		{
+			if paragraph_start || !open { opening.push( i ) } else { closing.push( i ) }
+
+			if !paragraph_start { open = !open }
+
+			paragraph_start = false;
+		}
+
+		else if !is_space( c ) { paragraph_start = false }
+	}
+
+	( opening, closing )
+}
+
+
+
+// Replace runs of breaking spaces before high punctuation and inside guillemets by a narrow no-break space. We do this by
+// setting the unbroken text of the split spanning the spaces, adding a disabled split if no generator proposed one.
+//
+fn narrow( text: &str, splits: &mut Vec<SplitPoint> )
+{
+	let mut runs  = Vec::new();
+	let mut start = None;
+
+	for ( i, c ) in text.char_indices()
+	{
+		if util::char_is_whitespace( &c )  &&  !util::char_is_line_break( &c )
+		{
+			if start.is_none() { start = Some( i ) }
+
+			continue;
+		}
+
+		if let Some( s ) = start.take()
+		{
+			let before = text[ ..s ].chars().next_back();
 
-			if d.is_some() && NO_BREAK_AFTER.contains( &d.unwrap() )
+			if NO_BREAK_BEFORE.contains( &c )  ||  before.map_or( false, |b| NO_BREAK_AFTER.contains( &b ) )
 			{
-				split.enabled = false
+				runs.push(( s, i ));
 			}
+		}
+	}
+
+
+	for ( start, end ) in runs
+	{
+		let nbsp = NARROW_NO_BREAK.to_string();
 
+		if let Some( split ) = splits.iter_mut().find( |s| s.start.0 == start  &&  s.end.0 == end )
+		{
+			split.unbroken = Some( nbsp );
+			continue;
 		}
+
+		let mut split    = SplitPoint::new( start, end, 0 );
+		split.enabled    = false;
+		split.unbroken   = Some( nbsp );
+
+		splits.push( split );
 	}
 }
 
 
+
 #[cfg(test)]
 mod tests
 {
@@ -53,7 +198,7 @@ mod tests
 	{
 		let mut opp = Xi{ priority: 0 }.opportunities( &text );
 
-		French.run( &text, &mut opp );
+		French::default().run( &text, &mut opp );
 
 		println!( "{:?}", opp );
 
@@ -88,4 +233,102 @@ mod tests
 		assert!( !filter( s )[ 1 ].enabled );
 	}
 
+
+	#[test]
+	fn dont_break_before_percent()
+	{
+		let s = "25 % des voix";
+
+		assert!( !filter( s )[ 0 ].enabled );
+		assert!(  filter( s )[ 1 ].enabled );
+	}
+
+
+	#[test]
+	fn look_past_whitespace()
+	{
+		// Xi gives overlapping splits here, 1-2 and 1-4. The first one ends right before the tab.
+		//
+		let s = "a \t »";
+
+		assert!( filter( s ).iter().filter( |s| !s.mandatory ).all( |s| !s.enabled ) );
+	}
+
+
+	#[test]
+	fn dialogue()
+	{
+		let s = "— Oui, dit-il.";
+
+		assert!( !filter( s )[ 0 ].enabled );
+		assert!(  filter( s )[ 1 ].enabled );
+	}
+
+
+	#[test]
+	fn incise()
+	{
+		// splits: before the opening dash, after it, before the closing dash and after it.
+		//
+		let s = "Il — dit — part";
+		let f = filter( s );
+
+		assert!(  f[ 0 ].enabled );
+		assert!( !f[ 1 ].enabled );
+		assert!( !f[ 2 ].enabled );
+		assert!(  f[ 3 ].enabled );
+	}
+
+
+	// Any line break starts a new paragraph, so the dash after it starts a dialogue rather than closing the incise.
+	//
+	#[test]
+	fn dialogue_after_line_breaks()
+	{
+		for s in &[ "Il — dit\r— Oui", "Il — dit\u{2028}— Oui", "Il — dit\u{85}— Oui" ]
+		{
+			let f     = filter( s );
+			let after = s.rfind( '—' ).unwrap() + EM_DASH.len_utf8();
+
+			assert!( !f.iter().find( |s| s.start.0 == after ).unwrap().enabled );
+		}
+	}
+
+
+	#[test]
+	fn mandatory_stays_enabled()
+	{
+		let s = "a\n»";
+
+		assert!( filter( s )[ 0 ].enabled );
+	}
+
+
+	#[test]
+	fn narrow_nbsp()
+	{
+		let s       = "« a » b !";
+		let mut opp = Xi{ priority: 0 }.opportunities( &s );
+
+		French{ narrow_nbsp: true }.run( &s, &mut opp );
+
+		let nbsp = Some( NARROW_NO_BREAK.to_string() );
+
+		assert_eq!( opp.iter().filter( |s| s.unbroken == nbsp ).count(), 3 );
+		assert!   ( opp.iter().filter( |s| s.unbroken == nbsp ).all( |s| !s.enabled ) );
+	}
+
+
+	#[test]
+	fn narrow_nbsp_other_spaces()
+	{
+		let s       = "«\ta» b\u{2009}!";
+		let mut opp = Xi{ priority: 0 }.opportunities( &s );
+
+		French{ narrow_nbsp: true }.run( &s, &mut opp );
+
+		let nbsp = Some( NARROW_NO_BREAK.to_string() );
+
+		assert_eq!( opp.iter().filter( |s| s.unbroken == nbsp ).count(), 2 );
+	}
 }
@@ -1,8 +1,43 @@
-pub mod french;
+pub mod binding      ;
+pub mod french       ;
+pub mod single_letter;
+pub mod pattern      ;
+pub mod combinator   ;
 
 pub use super::*;
 
-pub trait Filter
+// Like generators, filters are shared by every thread that uses the Wrapper, so they have to be Send and Sync.
+//
+// Filters may change, remove, add and reorder the splits they get.
+//
+pub trait Filter: Send + Sync
 {
 	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> );
+
+
+	// Identifies this filter in SplitPoint::filters.
+	//
+	fn name( &self ) -> &str { "" }
+
+
+	// Whether what the filter does to the splits of a paragraph only depends on the text of that paragraph, between the
+	// mandatory breaks around it. See Generate::local, Wrapper::edit relies on both.
+	//
+	fn local( &self ) -> bool { true }
+}
+
+
+
+// Any closure that takes the text and the split points is a filter, for one-off rules. We can't tell what it looks at, so
+// it's not local.
+//
+impl< F > Filter for F where F: Fn( &str, &mut Vec<SplitPoint> ) + Send + Sync
+{
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		self( text, splits )
+	}
+
+
+	fn local( &self ) -> bool { false }
 }
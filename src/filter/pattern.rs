@@ -0,0 +1,84 @@
+use regex::Regex;
+
+pub use super::*;
+
+use self::Filter;
+
+
+// Disables the splits that fall inside a match of a regular expression, for house rules like "never break inside [[wiki
+// links]]". Splits at either end of a match stay, and so do mandatory ones. Matches may span line breaks, so it's not local.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Pattern
+{
+	pub regex: Regex
+}
+
+
+
+impl Pattern
+{
+	pub fn new( pattern: &str ) -> Result< Pattern, &'static str >
+	{
+		let regex = Regex::new( pattern ).map_err( |_| "Invalid regular expression" )?;
+
+		Ok( Pattern{ regex: regex } )
+	}
+}
+
+
+
+impl Filter for Pattern
+{
+	fn name( &self ) -> &str { "pattern" }
+
+
+	fn local( &self ) -> bool { false }
+
+
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		for m in self.regex.find_iter( text )
+		{
+			for split in splits.iter_mut()
+			{
+				if split.mandatory { continue }
+
+				if split.start.0 > m.start()  &&  split.end.0 < m.end() { split.enabled = false }
+			}
+		}
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use generator::unicode_standard::Xi;
+
+	fn filter( text: &str, pattern: &str ) -> Vec< bool >
+	{
+		let mut opp = Xi{ priority: 0 }.opportunities( &text );
+
+		Pattern::new( pattern ).unwrap().run( &text, &mut opp );
+
+		opp.iter().filter( |s| !s.mandatory ).map( |s| s.enabled ).collect()
+	}
+
+
+	#[test]
+	fn wiki_links()
+	{
+		assert_eq!( filter( "see [[the main page]] now", r"\[\[.*?\]\]" ), vec![ true, false, false, true ] );
+	}
+
+
+	#[test]
+	fn placeholders()
+	{
+		assert_eq!( filter( "hi {{first name}}, {{last name}}", r"\{\{.*?\}\}" ), vec![ true, false, true, false ] );
+	}
+}
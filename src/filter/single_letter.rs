@@ -0,0 +1,172 @@
+pub use super::*;
+
+use self::Filter;
+
+// One letter prepositions and conjunctions that may not end a line.
+//
+pub const CZECH : &'static [&'static str] = &[ "a", "i", "k", "o", "s", "u", "v", "z" ];
+pub const SLOVAK: &'static [&'static str] = &[ "a", "i", "k", "o", "s", "u", "v", "z" ];
+pub const POLISH: &'static [&'static str] = &[ "a", "i", "o", "u", "w", "z" ];
+
+
+// Czech, Slovak and Polish typography forbid leaving one letter words at the end of a line. This filter disables splits
+// right after any of the words in the list, comparing case insensitively.
+//
+// If penalty is set, the splits are not disabled, but that amount is subtracted from their score instead, see
+// SplitPoint::score. Other splits that fit on the line win, but when there are none we still break after the word.
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct SingleLetter
+{
+	pub words  : Vec< String >  ,
+	pub penalty: Option< usize > ,
+}
+
+
+
+impl SingleLetter
+{
+	pub fn new( words: &[&str] ) -> SingleLetter
+	{
+		SingleLetter
+		{
+			words  : words.iter().map( |w| w.to_lowercase() ).collect() ,
+			penalty: None                                                ,
+		}
+	}
+
+
+	pub fn czech () -> SingleLetter { SingleLetter::new( CZECH  ) }
+	pub fn slovak() -> SingleLetter { SingleLetter::new( SLOVAK ) }
+	pub fn polish() -> SingleLetter { SingleLetter::new( POLISH ) }
+}
+
+
+
+impl Filter for SingleLetter
+{
+	fn name( &self ) -> &str { "single_letter" }
+
+
+	fn run( &self, text: &str, splits: &mut Vec<SplitPoint> )
+	{
+		let after: Vec<bool> = splits.iter().map( |split|
+		{
+			// Allow for opening punctuation, like "(v Praze)".
+			//
+			!split.mandatory && self.words.contains
+			(
+				&util::word_before( text, split.start.0 ).trim_left_matches( |c: char| !c.is_alphanumeric() ).to_lowercase()
+			)
+
+		}).collect();
+
+
+		for ( split, _ ) in splits.iter_mut().zip( &after ).filter( |&( _, a )| *a )
+		{
+			match self.penalty
+			{
+				Some( penalty ) => split.penalty = split.penalty + penalty ,
+				None            => split.enabled = false                   ,
+			}
+		}
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use generator::unicode_standard::Xi;
+
+	fn filter( text: &str, filter: &SingleLetter ) -> Vec< SplitPoint >
+	{
+		let mut opp = Xi{ priority: 5 }.opportunities( &text );
+
+		filter.run( &text, &mut opp );
+
+		opp
+	}
+
+
+	#[test]
+	fn czech()
+	{
+		let f = filter( "Byl v Praze a Brně", &SingleLetter::czech() );
+
+		assert!(  f[ 0 ].enabled );
+		assert!( !f[ 1 ].enabled );
+		assert!(  f[ 2 ].enabled );
+		assert!( !f[ 3 ].enabled );
+	}
+
+
+	#[test]
+	fn case_and_punctuation()
+	{
+		let f = filter( "(W domu) Z nim", &SingleLetter::polish() );
+
+		assert!( !f[ 0 ].enabled );
+		assert!(  f[ 1 ].enabled );
+		assert!( !f[ 2 ].enabled );
+	}
+
+
+	#[test]
+	fn custom_words()
+	{
+		let f = filter( "ve městě", &SingleLetter::new( &[ "ve" ] ) );
+
+		assert!( !f[ 0 ].enabled );
+	}
+
+
+	#[test]
+	fn penalty()
+	{
+		let mut sl = SingleLetter::czech();
+		sl.penalty = Some( 3 );
+
+		let f = filter( "Byl v Praze", &sl );
+
+		assert!   ( f[ 1 ].enabled                    );
+		assert_eq!( f[ 0 ].penalty, WidthOffset( 0 ) );
+		assert_eq!( f[ 1 ].penalty, WidthOffset( 3 ) );
+		assert_eq!( f[ 1 ].priority, WidthOffset( 5 ) );
+	}
+
+
+	#[test]
+	fn penalty_at_priority_zero()
+	{
+		let mut sl = SingleLetter::czech();
+		sl.penalty = Some( 3 );
+
+		let mut f = Xi{ priority: 0 }.opportunities( "Byl v Praze" );
+		let mut g = f.clone();
+
+		sl.run( "Byl v Praze", &mut f );
+
+		for split in f.iter_mut().chain( g.iter_mut() ) { split.width = Some( WidthOffset( split.start.0 ) ) }
+
+		let ruler = ruler::unicode_width::UnicodeWidth;
+
+		assert!   ( f[ 1 ].enabled                                     );
+		assert_eq!( f[ 0 ].score( &ruler ), g[ 0 ].score( &ruler )     );
+		assert_eq!( f[ 1 ].score( &ruler ), g[ 1 ].score( &ruler ) - 3 );
+
+
+		// The split after "v" fits, but the one after "Byl" scores better now. When nothing else fits, we still break after "a".
+		//
+		let wrapper = Builder::new( 7 ).generator( Xi{ priority: 0 } ).filter( sl.clone() ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "Byl v Praze" ), Ok( "Byl\nv Praze".to_string() ) );
+
+		let wrapper = Builder::new( 1 ).generator( Xi{ priority: 0 } ).filter( sl ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "a b" ), Ok( "a\nb".to_string() ) );
+	}
+}
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use super::*;
+
+use self::Generate;
+
+
+// Building blocks to make generators out of others. Splits keep the name of the generator that proposed them, see
+// SplitPoint::generator.
+
+
+// Give all splits of a generator another priority.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Prioritise< G >
+{
+	pub generator: G     ,
+	pub priority : usize ,
+}
+
+
+
+impl< G > Generate for Prioritise< G > where G: Generate
+{
+	fn name ( &self ) -> &str { self.generator.name()  }
+	fn local( &self ) -> bool { self.generator.local() }
+
+
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		let mut splits = named( &self.generator, text );
+
+		for split in &mut splits { split.priority = WidthOffset( self.priority ) }
+
+		splits
+	}
+}
+
+
+
+// Only keep the splits of a generator that lie within a byte range of the text. Mandatory splits are always kept, since
+// restricting where we may break doesn't remove the newlines from the text.
+//
+// The range is in the text given to the Wrapper. Since an edit moves text in and out of it, Wrapper::edit harvests the whole
+// text again when there is a Restrict among the generators.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Restrict< G >
+{
+	pub generator: G              ,
+	pub range    : Range< usize > ,
+}
+
+
+
+impl< G > Generate for Restrict< G > where G: Generate
+{
+	fn name ( &self ) -> &str { self.generator.name() }
+	fn local( &self ) -> bool { false                 }
+
+
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		let range = &self.range;
+
+		named( &self.generator, text ).into_iter()
+
+			.filter( |s| s.mandatory  ||  ( s.start.0 >= range.start  &&  s.end.0 <= range.end ) )
+			.collect()
+	}
+}
+
+
+
+// Like Restrict, but for the byte ranges spans returns for the text, eg. the code blocks of a markdown document.
+//
+// Spans always gets the whole text, also with Wrapper::edit, since an edit in one paragraph can change the spans in others,
+// eg. by adding the start of a code block.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Spans< G, F >
+{
+	pub generator: G ,
+	pub spans    : F ,
+}
+
+
+
+impl< G, F > Generate for Spans< G, F >
+
+	where G: Generate                                           ,
+	      F: Fn( &str ) -> Vec< Range< usize > > + Send + Sync ,
+{
+	fn name ( &self ) -> &str { self.generator.name() }
+	fn local( &self ) -> bool { false                 }
+
+
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		let spans = ( self.spans )( text );
+
+		named( &self.generator, text ).into_iter()
+
+			.filter( |s| s.mandatory  ||  spans.iter().any( |r| s.start.0 >= r.start  &&  s.end.0 <= r.end ) )
+			.collect()
+	}
+}
+
+
+
+// The splits of all generators. When several propose a split with the same range, the first one wins.
+//
+pub struct Union
+{
+	pub generators: Vec< Box<Generate> >
+}
+
+
+
+impl Generate for Union
+{
+	fn name ( &self ) -> &str { "union"                                      }
+	fn local( &self ) -> bool { self.generators.iter().all( |g| g.local() ) }
+
+
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		let mut splits: Vec< SplitPoint >                   = Vec::new()    ;
+		let mut seen  : HashSet<( ByteOffset, ByteOffset )> = HashSet::new();
+
+		for generator in &self.generators
+		{
+			for split in named( &**generator, text )
+			{
+				if seen.insert(( split.start, split.end )) { splits.push( split ) }
+			}
+		}
+
+		splits
+	}
+}
+
+
+
+// The splits of the first generator that all the others propose as well, with the same range.
+//
+pub struct Intersection
+{
+	pub generators: Vec< Box<Generate> >
+}
+
+
+
+impl Generate for Intersection
+{
+	fn name ( &self ) -> &str { "intersection"                               }
+	fn local( &self ) -> bool { self.generators.iter().all( |g| g.local() ) }
+
+
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		let mut generators = self.generators.iter();
+
+		let mut splits = match generators.next()
+		{
+			Some( first ) => named( &**first, text ),
+			None          => return Vec::new()      ,
+		};
+
+		for generator in generators
+		{
+			let other: HashSet<( ByteOffset, ByteOffset )> = generator.opportunities( text ).iter().map( |o| ( o.start, o.end ) ).collect();
+
+			splits.retain( |s| other.contains( &( s.start, s.end ) ) );
+		}
+
+		splits
+	}
+}
+
+
+
+// The splits of a generator, named after it unless it named them itself.
+//
+fn named< G: Generate + ?Sized >( generator: &G, text: &str ) -> Vec< SplitPoint >
+{
+	let mut splits = generator.opportunities( text );
+
+	for split in &mut splits
+	{
+		if split.generator.is_empty() { split.generator = generator.name().to_string() }
+	}
+
+	splits
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use generator::unicode_standard::Xi;
+	use generator::pattern::Pattern;
+
+	fn starts( splits: Vec< SplitPoint > ) -> Vec< usize >
+	{
+		splits.iter().filter( |s| !s.mandatory ).map( |s| s.start.0 ).collect()
+	}
+
+
+	#[test]
+	fn prioritise()
+	{
+		let splits = Prioritise{ generator: Xi{ priority: 0 }, priority: 7 }.opportunities( "a b" );
+
+		assert!   ( splits.iter().all( |s| s.priority == WidthOffset( 7 ) ) );
+		assert_eq!( splits[ 0 ].generator, "xi" );
+	}
+
+
+	#[test]
+	fn restrict()
+	{
+		let splits = Restrict{ generator: Xi{ priority: 0 }, range: 2..6 }.opportunities( "a b c d\ne" );
+
+		assert_eq!( starts( splits.clone() ), vec![ 3, 5 ] );
+		assert_eq!( splits.iter().filter( |s| s.mandatory ).count(), 2 );
+	}
+
+
+	#[test]
+	fn spans()
+	{
+		let quoted = |text: &str| text.match_indices( '"' ).collect::< Vec<_> >().chunks( 2 )
+
+			.filter( |pair| pair.len() == 2 )
+			.map( |pair| pair[ 0 ].0..pair[ 1 ].0 )
+			.collect()
+		;
+
+		let splits = Spans{ generator: Xi{ priority: 0 }, spans: quoted }.opportunities( r#"a "b c" d "e f""# );
+
+		assert_eq!( starts( splits ), vec![ 4, 12 ] );
+	}
+
+
+	#[test]
+	fn union()
+	{
+		let union = Union{ generators: vec![ Box::new( Xi{ priority: 0 } ), Box::new( Pattern::new( "->|( )" ).unwrap() ) ] };
+
+		let splits = union.opportunities( "a->b c" );
+
+		// Xi breaks after the hyphen, the pattern after the arrow. The space is Xi's.
+		//
+		assert_eq!( starts( splits.clone() ), vec![ 2, 4, 3 ] );
+		assert_eq!( splits[ 1 ].generator, "xi"      );
+		assert_eq!( splits[ 3 ].generator, "pattern" );
+	}
+
+
+	#[test]
+	fn intersection()
+	{
+		let both = Intersection{ generators: vec![ Box::new( Xi{ priority: 0 } ), Box::new( Pattern::new( "b( )" ).unwrap() ) ] };
+
+		assert_eq!( starts( both.opportunities( "a b c b d" ) ), vec![ 3, 7 ] );
+	}
+}
@@ -1,4 +1,7 @@
-use hyphenation_crate::{FullTextHyphenation, Corpus};
+use std::sync::Arc;
+
+use hyphenation_crate::{FullTextHyphenation, Hyphenation, Corpus};
+use unicode_segmentation::UnicodeSegmentation;
 use super::*;
 
 use self::Generate;
@@ -8,23 +11,115 @@ use self::Generate;
 //
 pub struct Hyphenator
 {
-	pub priority: usize  ,
-	pub corpus  : Corpus ,
-	pub glue    : String ,
+	pub priority: usize         ,
+	pub corpus  : Arc< Corpus > , // Dictionaries are big, so several hyphenators can share one.
+	pub glue    : String        ,
+	pub quality : Quality       ,
+}
+
+
+
+// Typographic restrictions on which words get hyphenated and where. Lengths are counted in grapheme clusters.
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct Quality
+{
+	pub left_min   : usize , // The minimum amount of characters before the hyphen.
+	pub right_min  : usize , // The minimum amount of characters on the next line.
+	pub capitalised: bool  , // Whether to hyphenate words starting with an uppercase letter, like names.
+	pub acronyms   : bool  , // Whether to hyphenate words written in all uppercase.
+	pub digits     : bool  , // Whether to hyphenate words containing digits.
+	pub last_word  : bool  , // Whether to hyphenate the last word of a paragraph.
+}
+
+
+
+impl Default for Quality
+{
+	fn default() -> Quality
+	{
+		Quality
+		{
+			left_min   : 2    ,
+			right_min  : 2    ,
+			capitalised: true ,
+			acronyms   : true ,
+			digits     : true ,
+			last_word  : true ,
+		}
+	}
+}
+
+
+
+impl Quality
+{
+	// Whether we may hyphenate the word containing byte_offset at that position.
+	//
+	pub fn allows( &self, text: &str, byte_offset: usize ) -> bool
+	{
+		let start = text[ ..byte_offset ].grapheme_indices( true ).rev().take_while( |&( _, g )| is_letter( g ) ).last()
+
+			.map_or( byte_offset, |( i, _ )| i )
+		;
+
+		let end = text[ byte_offset.. ].grapheme_indices( true ).take_while( |&( _, g )| is_letter( g ) ).last()
+
+			.map_or( byte_offset, |( i, g )| byte_offset + i + g.len() )
+		;
+
+		let word = &text[ start..end ];
+
+
+		if text[ start      ..byte_offset ].graphemes( true ).count() < self.left_min  { return false }
+		if text[ byte_offset..end         ].graphemes( true ).count() < self.right_min { return false }
+
+		if !self.capitalised && word.chars().next().map_or( false, char::is_uppercase ) { return false }
+
+		if !self.acronyms
+		&&  word.chars().filter( |c| c.is_alphabetic() ).count() > 1
+		&&  word.chars().filter( |c| c.is_alphabetic() ).all( char::is_uppercase )
+		{
+			return false
+		}
+
+		if !self.digits && word.chars().any( char::is_numeric ) { return false }
+
+
+		// Nothing but punctuation and whitespace up to the end of the paragraph.
+		//
+		if !self.last_word
+		{
+			let rest = text[ end.. ].split( |c: char| util::char_is_line_break( &c ) ).next().unwrap_or( "" );
+
+			if !rest.chars().any( char::is_alphanumeric ) { return false }
+		}
+
+		true
+	}
+}
+
+
+
+fn is_letter( grapheme: &str ) -> bool
+{
+	grapheme.chars().next().map_or( false, char::is_alphanumeric )
 }
 
 
 
 impl Generate for Hyphenator
 {
+	fn name( &self ) -> &str { "hyphenation" }
+
+
 	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
 	{
 		let list = text.fulltext_opportunities( &self.corpus );
 
-		let result = list.iter().map( |byte_offset|
+		let result = list.iter().filter( |byte_offset| self.quality.allows( text, **byte_offset ) ).map( |byte_offset|
 		{
-			if cfg!( debug_assertions ) { println!("break from Hyphenator: {:?}", byte_offset ) }
-
 			let mut s = SplitPoint::new( *byte_offset, *byte_offset, self.priority );
 			s.glue = self.glue.clone();
 			s
@@ -34,6 +129,55 @@ impl Generate for Hyphenator
 
 		result
 	}
+
+
+	// Remove the hyphen of our glue when the text before the line break ends in it, but only if the dictionary says the word
+	// can be hyphenated there. Otherwise it's a hyphen that was in the text, as in "well-known". The word might continue
+	// over more lines we hyphenated, which may end in "\r\n" like unwrap allows.
+	//
+	fn rejoin( &self, before: &str, after: &str ) -> Option<( usize, String )>
+	{
+		let mark = self.glue.trim_right_matches( |c| c == '\n' || c == '\r' );
+
+		if mark.is_empty()  ||  !before.ends_with( mark ) { return None }
+
+		let letters = |text: &str| text.chars().take_while( |c| c.is_alphabetic() ).map( char::len_utf8 ).sum::< usize >();
+
+		let line = &before[ ..before.len() - mark.len() ];
+		let left = &line[ line.len() - line.chars().rev().take_while( |c| c.is_alphabetic() ).map( char::len_utf8 ).sum::< usize >().. ];
+
+		let mut word  = left.to_string();
+		let mut after = after;
+
+		loop
+		{
+			let right = letters( after );
+
+			word.push_str( &after[ ..right ] );
+			after = &after[ right.. ];
+
+			if !after.starts_with( mark ) { break }
+
+			let rest = &after[ mark.len().. ];
+			let rest = if rest.starts_with( "\r\n" ) { &rest[ 1.. ] } else { rest };
+
+			match rest.starts_with( '\n' )
+			{
+				true  => after = &rest[ 1.. ],
+				false => break,
+			}
+		}
+
+
+		if left.is_empty()  ||  word.len() == left.len() { return None }
+
+		if word.as_str().opportunities( &self.corpus ).contains( &left.len() )
+		{
+			return Some(( mark.len(), String::new() ))
+		}
+
+		None
+	}
 }
 
 
@@ -55,7 +199,7 @@ mod tests
 
 		assert_eq!
 		(
-			  Hyphenator{ priority: 0, glue: "-".to_string(), corpus: c }.opportunities( &s )
+			  Hyphenator{ priority: 0, glue: "-".to_string(), corpus: Arc::new( c ), quality: Quality::default() }.opportunities( &s )
 
 			, vec![ s1, s2 ]
 		);
@@ -72,7 +216,7 @@ mod tests
 
 		assert_eq!
 		(
-			  Hyphenator{ priority: 0, glue: "-".to_string(), corpus: c }.opportunities( &s )
+			  Hyphenator{ priority: 0, glue: "-".to_string(), corpus: Arc::new( c ), quality: Quality::default() }.opportunities( &s )
 
 			, vec![ s1, s2 ]
 		);
@@ -89,9 +233,69 @@ mod tests
 
 		assert_eq!
 		(
-			  Hyphenator{ priority: 0, glue: "-".to_string(), corpus: c }.opportunities( &s )
+			  Hyphenator{ priority: 0, glue: "-".to_string(), corpus: Arc::new( c ), quality: Quality::default() }.opportunities( &s )
 
 			, vec![]
 		);
 	}
+
+
+	fn quality( text: &str, quality: Quality ) -> Vec< usize >
+	{
+		let c = hyphenation_crate::load( Language::English_US ).unwrap();
+
+		Hyphenator{ priority: 0, glue: "-".to_string(), corpus: Arc::new( c ), quality: quality }
+
+			.opportunities( text ).iter().map( |s| s.start.0 ).collect()
+	}
+
+
+	#[test]
+	fn left_and_right_min()
+	{
+		assert_eq!( quality( "hyphenation", Quality{ left_min: 3, ..Quality::default() } ), vec![ 6 ] );
+		assert_eq!( quality( "hyphenation", Quality{ right_min: 6, ..Quality::default() } ), vec![ 2 ] );
+	}
+
+
+	#[test]
+	fn capitalised_and_acronyms()
+	{
+		assert_eq!( quality( "Hyphenation", Quality{ capitalised: false, ..Quality::default() } ), Vec::< usize >::new() );
+		assert_eq!( quality( "Hyphenation", Quality{ acronyms   : false, ..Quality::default() } ), vec![ 2, 6 ]           );
+		assert_eq!( quality( "HYPHENATION", Quality{ acronyms   : false, ..Quality::default() } ), Vec::< usize >::new() );
+	}
+
+
+	#[test]
+	fn last_word()
+	{
+		let q = Quality{ last_word: false, ..Quality::default() };
+
+		assert_eq!( quality( "hyphenation hyphenation."   , q.clone() ), vec![ 2, 6 ]         );
+		assert_eq!( quality( "hyphenation.\nhyphenation a", q.clone() ), vec![ 15, 19 ]       );
+
+		// Any line break ends the paragraph.
+		//
+		assert_eq!( quality( "hyphenation.\rhyphenation a"      , q.clone() ), vec![ 15, 19 ] );
+		assert_eq!( quality( "hyphenation.\u{2028}hyphenation a", q.clone() ), vec![ 17, 21 ] );
+		assert_eq!( quality( "hyphenation.\u{85}hyphenation a"  , q         ), vec![ 16, 20 ] );
+	}
+
+
+	#[test]
+	fn rejoin()
+	{
+		let hyph = Hyphenator{ priority: 0, corpus: Arc::new( hyphenation_crate::load( Language::English_US ).unwrap() ), glue: "-\n".to_string(), quality: Default::default() };
+
+		assert_eq!( hyph.rejoin( "the hyphen-", "ation is" ), Some(( 1, String::new() )) );
+		assert_eq!( hyph.rejoin( "the hyp-"   , "henation" ), None );
+		assert_eq!( hyph.rejoin( "the hyphen" , "ation"    ), None );
+		assert_eq!( hyph.rejoin( "-"          , "ation"    ), None );
+
+		// The word goes on over the next line, which may end in "\r\n". "commu" alone has no hyphens, so this needs the whole word.
+		//
+		assert_eq!( hyph.rejoin( "the com-", "mu-\nnication is"   ), Some(( 1, String::new() )) );
+		assert_eq!( hyph.rejoin( "the com-", "mu-\r\nnication is" ), Some(( 1, String::new() )) );
+	}
 }
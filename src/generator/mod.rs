@@ -1,9 +1,44 @@
 pub mod unicode_standard ;
 pub mod hyphenation      ;
+pub mod pattern          ;
+pub mod combinator       ;
 
 pub use super::*;
 
-pub trait Generate
+// Generators are shared by every thread that uses the Wrapper, so they have to be Send and Sync.
+//
+pub trait Generate: Send + Sync
 {
 	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >;
+
+
+	// Identifies this generator in SplitPoint::generator, so filters and diagnostics can tell where a split comes from.
+	//
+	fn name( &self ) -> &str { "" }
+
+
+	// Whether the splits for a paragraph only depend on the text of that paragraph, between the mandatory breaks around it.
+	// Wrapper::edit relies on this to only harvest the paragraphs an edit touches. When a generator says no, it harvests the
+	// whole text again.
+	//
+	fn local( &self ) -> bool { true }
+
+
+	// Used to unwrap text that was wrapped before. Before is the text up to a line break, with earlier line breaks already
+	// undone, and after is the text after it. If this generator could have broken the text there, returns how many bytes
+	// at the end of before are glue, and what to put in their place instead of the line break.
+	//
+	fn rejoin( &self, _before: &str, _after: &str ) -> Option<( usize, String )> { None }
+}
+
+
+
+// Any closure that takes the text and returns split points is a generator, for one-off rules.
+//
+impl< F > Generate for F where F: Fn( &str ) -> Vec< SplitPoint > + Send + Sync
+{
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		self( text )
+	}
 }
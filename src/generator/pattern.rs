@@ -0,0 +1,107 @@
+use regex::Regex;
+
+use super::*;
+
+use self::Generate;
+
+
+// Proposes a split for every match of a regular expression, for house rules like "allow a break after ->".
+//
+// If the expression has a capture group, the split spans what the first group matched, which is removed when we cut there,
+// like the whitespace of Xi. Otherwise the split is at the end of the match. To break before "->", use "()->".
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Pattern
+{
+	pub regex   : Regex  ,
+	pub glue    : String ,
+	pub priority: usize  ,
+}
+
+
+
+impl Pattern
+{
+	pub fn new( pattern: &str ) -> Result< Pattern, &'static str >
+	{
+		let regex = Regex::new( pattern ).map_err( |_| "Invalid regular expression" )?;
+
+		Ok( Pattern
+		{
+			regex   : regex            ,
+			glue    : "\n".to_string() ,
+			priority: 0                ,
+		})
+	}
+}
+
+
+
+impl Generate for Pattern
+{
+	fn name( &self ) -> &str { "pattern" }
+
+
+	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
+	{
+		self.regex.captures_iter( text ).filter_map( |captures|
+		{
+			let ( start, end ) = match captures.get( 1 )
+			{
+				Some( group ) => ( group.start(), group.end() ),
+				None          => { let m = captures.get( 0 ).unwrap(); ( m.end(), m.end() ) }
+			};
+
+			// Breaking at either end of the text makes no sense.
+			//
+			if end == 0  ||  start == text.len() { return None }
+
+			let mut s = SplitPoint::new( start, end, self.priority );
+			s.glue = self.glue.clone();
+
+			Some( s )
+		})
+
+		.collect()
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+
+	#[test]
+	fn after_match()
+	{
+		let found = Pattern::new( "->" ).unwrap().opportunities( "a->b->" );
+
+		assert_eq!( found, vec![ SplitPoint::new( 3, 3, 0 ) ] );
+	}
+
+
+	#[test]
+	fn group()
+	{
+		let mut pattern = Pattern::new( r"\w( +)\|" ).unwrap();
+
+		pattern.glue     = " \\\n".to_string();
+		pattern.priority = 2;
+
+		let mut expect = SplitPoint::new( 1, 3, 2 );
+		expect.glue    = " \\\n".to_string();
+
+		assert_eq!( pattern.opportunities( "a  | b" ), vec![ expect ] );
+	}
+
+
+	#[test]
+	fn invalid()
+	{
+		assert_eq!( Pattern::new( "(" ).unwrap_err(), "Invalid regular expression" );
+	}
+}
@@ -4,6 +4,13 @@ use super::*;
 
 use self::Generate;
 
+// Invisible characters that control line breaking. They shouldn't make it to the output, unless as a visible hyphen for a
+// soft hyphen we break at.
+//
+const SOFT_HYPHEN     : char = '\u{AD}'  ;
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+const WORD_JOINER     : char = '\u{2060}';
+
 
 #[ derive( PartialEq, Eq, Clone, Debug ) ]
 //
@@ -16,13 +23,18 @@ pub struct Xi
 
 impl Generate for Xi
 {
+	fn name( &self ) -> &str { "xi" }
+
+
 	fn opportunities( &self, text: &str ) -> Vec< SplitPoint >
 	{
-		LineBreakIterator::new( text ).map( |(byte_offset, hard)|
+		let mut splits = LineBreakIterator::new( text ).map( |(byte_offset, hard)|
 		{
-			let mut start = byte_offset;
-			let mut end   = byte_offset;
-			let mut glue  = String::with_capacity( 2 );
+			let mut start    = byte_offset;
+			let mut end      = byte_offset;
+			let mut glue     = String::with_capacity( 2 );
+			let mut unbroken = None;
+			let mut enabled  = true;
 
 
 			// Recreate the glue for hard breaks and set the start index to before the chars we move to glue. The glue will be set
@@ -84,22 +96,54 @@ impl Generate for Xi
 					}
 				}
 
+				// A soft hyphen becomes a visible hyphen when we break after it and disappears otherwise. A zero width space
+				// disappears either way. At the start of a line there is nothing to break off, so they only disappear.
+				//
 				else
 				{
-					glue = "\n".to_string()
+					glue = "\n".to_string();
+
+					match text[ ..end ].chars().next_back()
+					{
+						Some( c ) if start == end  &&  ( c == SOFT_HYPHEN || c == ZERO_WIDTH_SPACE ) =>
+						{
+							start    = end - c.len_utf8();
+							unbroken = Some( String::new() );
+							enabled  = text[ ..start ].chars().next_back().map_or( false, |c| !util::char_is_line_break( &c ) );
+
+							if c == SOFT_HYPHEN { glue = "-\n".to_string() }
+						}
+
+						_ => {}
+					}
 				}
 			}
 
 
-			if cfg!( debug_assertions ) { println!("break from Xi: {:?}-{:?}", start, end ) }
 			let mut s = SplitPoint::new( start, end, self.priority );
 			s.mandatory = hard;
+			s.enabled   = enabled;
 			s.glue      = glue;
+			s.unbroken  = unbroken;
 
 			s
 		})
 
-		.collect::< Vec<_> >()
+		.collect::< Vec<_> >();
+
+
+		// Xi doesn't break around a word joiner, but we still need to remove it.
+		//
+		for ( i, c ) in text.char_indices().filter( |&( _, c )| c == WORD_JOINER )
+		{
+			let mut s  = SplitPoint::new( i, i + c.len_utf8(), self.priority );
+			s.enabled  = false;
+			s.unbroken = Some( String::new() );
+
+			splits.push( s );
+		}
+
+		splits
 
 
 		// The unicode annex 14 section 5 says that tab is to be considered like space when it comes to line breaking, however
@@ -114,6 +158,22 @@ impl Generate for Xi
 		// This means that for "foo \t bar", we will get overlaping split points, start: 3, end: 4 and start: 3, end 6.
 		// In principle, this does not lead to wrong splitting in the wrapping algorithm, so for now we don't correct this.
 	}
+
+
+	// Any line break could be ours. Join with a space, unless the line already ends in whitespace or we break between the
+	// two lines without consuming anything, as after a hyphen or between ideographs. We only look at the last and the first
+	// word around the line break.
+	//
+	fn rejoin( &self, before: &str, after: &str ) -> Option<( usize, String )>
+	{
+		let line = &before[ before.trim_right().rfind( char::is_whitespace ).map_or( 0, |i| i ).. ];
+		let next = after.split( '\n' ).next().unwrap_or( "" );
+		let text = format!( "{}{}", line, next );
+
+		let glued = self.opportunities( &text ).iter().any( |s| s.end.0 == line.len()  &&  !s.mandatory );
+
+		Some(( 0, if glued { String::new() } else { " ".to_string() } ))
+	}
 }
 
 
@@ -168,15 +228,59 @@ mod tests
 
 			, vec!
 			  [
-				  SplitPoint::new(  4,  4, 0 ) ,
-				  SplitPoint::new(  7,  7, 0 ) ,
-				  SplitPoint::new( 12, 12, 0 ) ,
-				  end            ( 14, 14, 0 ) ,
+				  soft           (  2,  4, "-\n" ) ,
+				  SplitPoint::new(  7,  7, 0      ) ,
+				  SplitPoint::new( 12, 12, 0      ) ,
+				  end            ( 14, 14, 0      ) ,
 			  ]
 		);
 	}
 
 
+	fn soft( start: usize, stop: usize, glue: &str ) -> SplitPoint
+	{
+		let mut result  = SplitPoint::new( start, stop, 0 );
+		result.glue     = glue.to_string();
+		result.unbroken = Some( String::new() );
+
+		result
+	}
+
+
+	// There is nothing to break off at the start of a line.
+	//
+	#[test]
+	fn soft_hyphen_at_line_start()
+	{
+		let mut first  = soft( 0, 2, "-\n" );
+		let mut second = soft( 4, 6, "-\n" );
+
+		first .enabled = false;
+		second.enabled = false;
+
+		let mut newline   = SplitPoint::new( 3, 4, 0 );
+		newline.mandatory = true;
+
+		assert_eq!( Xi{ priority: 0 }.opportunities( "\u{ad}5\n\u{ad}5" ), vec![ first, newline, second, end( 7, 7, 0 ) ] );
+	}
+
+
+	#[test]
+	fn zero_width()
+	{
+		let mut joiner     = SplitPoint::new( 3, 6, 0 );
+		joiner.enabled     = false;
+		joiner.unbroken    = Some( String::new() );
+
+		assert_eq!
+		(
+			  Xi{ priority: 0 }.opportunities( "foo\u{2060}bar\u{200B}baz" )
+
+			, vec![ soft( 9, 12, "\n" ), end( 15, 15, 0 ), joiner ]
+		);
+	}
+
+
 	// I don't know if this is really desirable behaviour, but in the worst case splits like this can be prevented with a filter.
 	//
 	#[test]
@@ -324,4 +428,16 @@ mod tests
 	// 		    ]
 	// 	);
 	// }
+
+
+	#[test]
+	fn rejoin()
+	{
+		let xi = Xi{ priority: 0 };
+
+		assert_eq!( xi.rejoin( "foo"  , "bar" ), Some(( 0, " ".to_string() )) );
+		assert_eq!( xi.rejoin( "foo " , "bar" ), Some(( 0, String::new()   )) );
+		assert_eq!( xi.rejoin( "well-", "known" ), Some(( 0, String::new() )) );
+		assert_eq!( xi.rejoin( "世界" , "你好" ), Some(( 0, String::new()   )) );
+	}
 }
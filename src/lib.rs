@@ -66,6 +66,10 @@ extern crate hyphenation          as hyphenation_crate  ;
 extern crate unicode_width        as unicode_width_crate;
 extern crate unicode_segmentation                       ;
 extern crate xi_unicode                                 ;
+extern crate regex                                      ;
+
+#[cfg(feature = "serde")] extern crate serde            ;
+#[cfg(feature = "rayon")] extern crate rayon            ;
 
 pub mod wrapper    ;
 pub mod generator  ;
@@ -74,15 +78,26 @@ pub mod splitpoint ;
 pub mod offset     ;
 pub mod util       ;
 pub mod ruler      ;
+pub mod trace      ;
+pub mod explain    ;
+pub mod builder    ;
+
+#[cfg(feature = "serde")] pub mod config;
 
 
 pub use splitpoint :: SplitPoint  ;
 pub use offset     :: ByteOffset  ;
 pub use offset     :: WidthOffset ;
 pub use wrapper    :: Wrapper     ;
+pub use builder    :: Builder     ;
+pub use wrapper    :: LastLine    ;
+pub use wrapper    :: Prepared    ;
+pub use wrapper    :: Layout      ;
+pub use wrapper    :: Line        ;
 pub use ruler      :: TextWidth   ;
 pub use generator  :: Generate    ;
 pub use filter     :: Filter      ;
+pub use trace      :: Observe     ;
 
 
 
@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::hash::{ Hash, Hasher };
 
 use super::*;
 
@@ -12,8 +13,12 @@ pub struct SplitPoint
 	pub glue     : String                ,
 	pub mandatory: bool                  ,
 	pub priority : WidthOffset           ,
+	pub penalty  : WidthOffset           , // Subtracted from the score, so filters can make a split less attractive but still usable.
 	pub width    : Option< WidthOffset > ,
 	pub enabled  : bool                  ,
+	pub unbroken : Option< String >      , // Replaces the text between start and end if the split is not used.
+	pub generator: String                , // The name of the generator that proposed this split, see Generate::name.
+	pub filters  : Vec< String >         , // The names of the filters that changed or added it, in order.
 }
 
 
@@ -33,25 +38,29 @@ impl SplitPoint
 			start    : ByteOffset ( start    ) ,
 			end      : ByteOffset ( end      ) ,
 			priority : WidthOffset( priority ) ,
+			penalty  : WidthOffset( 0        ) ,
 			glue     : "\n".to_string()        ,
 			mandatory: false                   ,
 			enabled  : true                    ,
 			width    : None                    ,
+			unbroken : None                    ,
+			generator: String::new()           ,
+			filters  : Vec::new()              ,
 		}
 	}
 
 
 	// We substract the width of the glue, so that if two splitpoints would otherwise have the same score,
-	// the one that doesn't need eg. hyphens wins.
+	// the one that doesn't need eg. hyphens wins. The penalty can take the score below zero, so it's signed.
 	//
-	pub fn score< Ruler: TextWidth >( &self, ruler: &Ruler ) -> WidthOffset
+	pub fn score< Ruler: TextWidth >( &self, ruler: &Ruler ) -> isize
 	{
 		if self.width.is_none() {
 
 			panic!( "Cannot calculate the score of a SplitPoint before setting it's width." ); }
 
 
-		self.width.unwrap() + self.priority - ruler.measure( &self.glue )
+		( self.width.unwrap() + self.priority - ruler.measure( &self.glue ) ).0 as isize - self.penalty.0 as isize
 	}
 }
 
@@ -90,6 +99,8 @@ impl PartialOrd for SplitPoint
 
 
 
+// Where a split comes from doesn't count, so that filters can be found out by comparing splits before and after they ran.
+//
 impl PartialEq for SplitPoint
 {
 	fn eq( &self, other: &Self ) -> bool
@@ -97,10 +108,32 @@ impl PartialEq for SplitPoint
 		self.start     == other.start     &&
 		self.end       == other.end       &&
 		self.priority  == other.priority  &&
+		self.penalty   == other.penalty   &&
 		self.glue      == other.glue      &&
 		self.enabled   == other.enabled   &&
 		self.mandatory == other.mandatory &&
-		self.width     == other.width
+		self.width     == other.width     &&
+		self.unbroken  == other.unbroken
+	}
+}
+
+
+
+// Consistent with PartialEq, so again without the provenance.
+//
+impl Hash for SplitPoint
+{
+	fn hash< H: Hasher >( &self, state: &mut H )
+	{
+		self.start    .hash( state );
+		self.end      .hash( state );
+		self.priority .hash( state );
+		self.penalty  .hash( state );
+		self.glue     .hash( state );
+		self.enabled  .hash( state );
+		self.mandatory.hash( state );
+		self.width    .hash( state );
+		self.unbroken .hash( state );
 	}
 }
 
@@ -118,9 +151,13 @@ mod tests
 		assert_eq!( s.start    , ByteOffset ( 1 ) );
 		assert_eq!( s.end      , ByteOffset ( 2 ) );
 		assert_eq!( s.priority , WidthOffset( 3 ) );
+		assert_eq!( s.penalty  , WidthOffset( 0 ) );
 		assert_eq!( s.glue     , "\n".to_string() );
 		assert_eq!( s.mandatory, false            );
 		assert_eq!( s.enabled  , true             );
+		assert_eq!( s.unbroken , None             );
+		assert_eq!( s.generator, ""               );
+		assert!   ( s.filters  .is_empty()        );
 	}
 
 
@@ -132,8 +169,11 @@ mod tests
 		s.width = Some( WidthOffset( 6 ) );
 		s.glue  = "-".to_string()         ;
 
-		assert_eq!( s.score( &ruler::unicode_width::UnicodeWidth ), WidthOffset ( 8 ) );
+		assert_eq!( s.score( &ruler::unicode_width::UnicodeWidth ), 8 );
+
+		s.penalty = WidthOffset( 10 );
 
+		assert_eq!( s.score( &ruler::unicode_width::UnicodeWidth ), -2 );
 	}
 
 
@@ -155,6 +195,18 @@ mod tests
 	}
 
 
+	#[test]
+	fn equal_should_have_same_penalty()
+	{
+		let     s = SplitPoint::new( 3, 5, 0 );
+		let mut t = SplitPoint::new( 3, 5, 0 );
+
+		t.penalty = WidthOffset( 1 );
+
+		assert_ne!( s, t );
+	}
+
+
 	#[test]
 	fn equal_should_have_same_mandatory()
 	{
@@ -179,6 +231,31 @@ mod tests
 	}
 
 
+	#[test]
+	fn equal_should_have_same_unbroken()
+	{
+		let     s = SplitPoint::new( 3, 5, 0 );
+		let mut t = SplitPoint::new( 3, 5, 0 );
+
+		t.unbroken = Some( "\u{202F}".to_string() );
+
+		assert_ne!( s, t );
+	}
+
+
+	#[test]
+	fn equal_ignores_provenance()
+	{
+		let     s = SplitPoint::new( 3, 5, 0 );
+		let mut t = SplitPoint::new( 3, 5, 0 );
+
+		t.generator = "xi".to_string();
+		t.filters.push( "french".to_string() );
+
+		assert_eq!( s, t );
+	}
+
+
 	// This is questionable, but for now mainly when we compare splitpoints it's in unit tests for generators. It probably
 	// doesn't make much sense to compare splitpoints from different strings, so let's say width needs to be the same.
 	//
@@ -0,0 +1,63 @@
+use super::*;
+
+
+// What a Wrapper tells its observer while wrapping, so one can see why a line broke where it did. Widths are in units of
+// the ruler and offsets refer to the complete text. See Wrapper::set_observer.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub enum Event<'a>
+{
+	// The width of the text up to a grapheme cluster.
+	//
+	Grapheme { offset: ByteOffset, width: WidthOffset, grapheme: &'a str },
+
+	// A split proposed by the generator at this index in the list of generators.
+	//
+	Generated { generator: usize, split: &'a SplitPoint },
+
+	// A split the filter at this index changed or, if before is None, added.
+	//
+	Filtered { filter: usize, before: Option< &'a SplitPoint >, after: &'a SplitPoint },
+
+	// We look for a split for the line starting at start, that has to end at end at the latest.
+	//
+	Line { start: WidthOffset, end: WidthOffset },
+
+	// A candidate for ending the current line, with its score.
+	//
+	Considered { split: &'a SplitPoint, score: isize },
+
+	// The split that won for the current line.
+	//
+	Chosen { split: &'a SplitPoint },
+
+	// Nothing fits, so we break the word at this split with the glue of the wrapper.
+	//
+	BrokenWord { split: &'a SplitPoint },
+}
+
+
+
+pub trait Observe: Send + Sync
+{
+	fn event( &self, event: &Event );
+}
+
+
+
+// Prints every event on stdout, like the wrapper used to do in debug builds.
+//
+#[ derive( PartialEq, Eq, Clone, Copy, Debug, Default ) ]
+//
+pub struct Print;
+
+
+
+impl Observe for Print
+{
+	fn event( &self, event: &Event )
+	{
+		println!( "{:?}", event );
+	}
+}
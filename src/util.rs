@@ -28,16 +28,45 @@ pub fn char_is_whitespace( c: &char ) -> bool
 
 // Unicode codepoints for characters that will consitute a hard break.
 //
-// const ENDLINE: &'static [char] = &
-// [
-// 	'\u{000A}' , // LINE FEED
-// 	'\u{000B}' , // LINE TABULATION (VT)
-// 	'\u{000C}' , // FORM FEED (FF)
-// 	'\u{000D}' , // CARRIAGE RETURN (CR)
-// 	'\u{0085}' , // NEXT LINE (NEL)
-// 	'\u{2028}' , // LINE SEPARATOR (LS)
-// 	'\u{2029}' , // PARAGRAPH SEPARATOR (PS)
-// ];
+const ENDLINE: &'static [char] = &
+[
+	'\u{000A}' , // LINE FEED
+	'\u{000B}' , // LINE TABULATION (VT)
+	'\u{000C}' , // FORM FEED (FF)
+	'\u{000D}' , // CARRIAGE RETURN (CR)
+	'\u{0085}' , // NEXT LINE (NEL)
+	'\u{2028}' , // LINE SEPARATOR (LS)
+	'\u{2029}' , // PARAGRAPH SEPARATOR (PS)
+];
+
+pub fn char_is_line_break( c: &char ) -> bool
+{
+	ENDLINE.contains( &c )
+}
+
+
+// Returns the word that ends right before the whitespace preceding offset, eg. for a split point, the word before the split.
+// A word here is anything between whitespace, so it includes punctuation. Words don't reach across line breaks, so there is
+// none before an offset that only has whitespace between it and a line break.
+//
+pub fn word_before( text: &str, offset: usize ) -> &str
+{
+	let end   = text[ ..offset ].trim_right_matches( |c: char| c.is_whitespace()  &&  !char_is_line_break( &c ) ).len();
+	let start = text[ ..end ].rfind( char::is_whitespace ).map_or( 0, |i| i + text[ i.. ].chars().next().unwrap().len_utf8() );
+
+	&text[ start..end ]
+}
+
+
+// Returns the word that starts right after the whitespace following offset, eg. for a split point, the word after the split.
+//
+pub fn word_after( text: &str, offset: usize ) -> &str
+{
+	let start = text.len() - text[ offset.. ].trim_left_matches( |c: char| c.is_whitespace()  &&  !char_is_line_break( &c ) ).len();
+	let end   = text[ start.. ].find( char::is_whitespace ).map_or( text.len(), |i| start + i );
+
+	&text[ start..end ]
+}
 
 
 
@@ -51,4 +80,26 @@ mod tests
 	{
 		assert!( char_is_whitespace( &' ' ) );
 	}
+
+
+	#[test]
+	fn word_before_and_after()
+	{
+		let s = "a  v\u{A0}x  Praze";
+
+		assert_eq!( word_before( s, 1  ), "a"     );
+		assert_eq!( word_before( s, 3  ), "a"     );
+		assert_eq!( word_before( s, 8  ), "x"     );
+		assert_eq!( word_before( s, 0  ), ""      );
+		assert_eq!( word_after ( s, 1  ), "v"     );
+		assert_eq!( word_after ( s, 7  ), "Praze" );
+		assert_eq!( word_after ( s, 14 ), ""      );
+
+		let s = "a\n v \r\nb";
+
+		assert_eq!( word_before( s, 2  ), ""      );
+		assert_eq!( word_before( s, 5  ), "v"     );
+		assert_eq!( word_after ( s, 2  ), "v"     );
+		assert_eq!( word_after ( s, 4  ), ""      );
+	}
 }
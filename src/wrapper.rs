@@ -1,21 +1,51 @@
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
+use std::ops::Range;
 
 use unicode_segmentation::UnicodeSegmentation;
 
+#[cfg(feature = "rayon")] use rayon::prelude::*;
+
 use super::*;
+use trace::Event;
+use explain::{ Explanation, Explained, Reason };
 
 
 pub struct Wrapper<Ruler>
 {
-	width     : usize                ,
-	generators: Vec< Box<Generate> > ,
-	filters   : Vec< Box<Filter>   > ,
-	ruler     : Ruler                ,
-	break_word: bool                 , // Whether to break a line even if no split point has been found.
-	glue      : String               , // What linebreak we should use if we have to create new split points for break_word
+	width     : usize                     ,
+	generators: Vec< Box<Generate> >      ,
+	filters   : Vec< Box<Filter>   >      ,
+	ruler     : Ruler                     ,
+	break_word: bool                      , // Whether to break a line even if no split point has been found.
+	glue      : String                    , // What linebreak we should use if we have to create new split points for break_word
+	ladder    : Option<( String, usize )> , // The maximum amount of consecutive lines ending in a split from this generator.
+	last_line : Option< LastLine >        , // The minimum content of the last line of a paragraph.
+	balanced  : bool                      , // Whether to make all lines about the same width.
+	observer  : Option< Box<Observe> >    , // Gets told about every step we take.
+}
+
+
+
+// How much the last line of a paragraph should at least hold, to avoid a single short word on it.
+//
+#[ derive( PartialEq, Eq, Clone, Copy, Debug ) ]
+#[ cfg_attr( feature = "serde", derive( ::serde::Serialize, ::serde::Deserialize ), serde( rename_all = "snake_case" ) ) ]
+//
+pub enum LastLine
+{
+	Width( usize ), // In units of the ruler.
+	Words( usize ),
 }
 
 
+
+// Break hints for Wrapper::annotate.
+//
+pub const SOFT_HYPHEN     : &'static str = "\u{AD}"  ;
+pub const ZERO_WIDTH_SPACE: &'static str = "\u{200B}";
+pub const WBR             : &'static str = "<wbr>"   ;
+
+
 impl<Ruler> Wrapper<Ruler> where Ruler: TextWidth
 {
 	pub fn new( width: usize, generators: Vec< Box<Generate> >, filters: Vec< Box<Filter> >, ruler: Ruler, break_word: bool )
@@ -34,6 +64,10 @@ impl<Ruler> Wrapper<Ruler> where Ruler: TextWidth
 				ruler     : ruler            ,
 				break_word: break_word       ,
 				glue      : "\n".to_string() ,
+				ladder    : None             ,
+				last_line : None             ,
+				balanced  : false            ,
+				observer  : None             ,
 			}
 		)
 	}
@@ -54,472 +88,2513 @@ impl<Ruler> Wrapper<Ruler> where Ruler: TextWidth
 	}
 
 
-	pub fn wrap( &self, line: &str ) -> Result< String, &'static str >
+	pub fn glue( &self ) -> &str { &self.glue }
+
+
+	// The glue for the splits we make when break_word has to cut a word that doesn't fit.
+	//
+	pub fn set_glue( &mut self, glue: &str )
 	{
-		// store byte to width conversion, because we will need to calculate our breakpoint in terms of display width.
-		//
-		let mut b2w: HashMap < ByteOffset , WidthOffset > = HashMap::with_capacity( line.len() );
-		let mut w2b: HashMap < WidthOffset, ByteOffset  > = HashMap::with_capacity( line.len() );
+		self.glue = glue.to_string();
+	}
 
-		let mut width = WidthOffset( 0 );
 
-		for ( bytes, graph ) in line.grapheme_indices( true )
-		{
-			b2w.insert( ByteOffset( bytes ), width                );
-			w2b.insert( width              , ByteOffset( bytes  ) );
+	pub fn ladder( &self ) -> &Option<( String, usize )> { &self.ladder }
 
-			if cfg!( debug_assertions ) { println!( "byte: {:02?}, width: {:02?}, graph:{:?}", bytes, width.0, graph ) }
 
-			width = width + self.ruler.measure( graph );
-		}
+	// Limit the number of consecutive lines that end in a split from the generator with the given name, typically
+	// "hyphenation" (see Generate::name and SplitPoint::generator). Once the limit is reached, such splits are only used on
+	// the next line if nothing else fits.
+	//
+	pub fn set_ladder( &mut self, ladder: Option<( String, usize )> )
+	{
+		self.ladder = ladder;
+	}
 
-		// Add the end of the string
-		//
-		b2w.insert( ByteOffset( line.len() ), width                     );
-		w2b.insert( width                   , ByteOffset( line.len()  ) );
 
-		if cfg!( debug_assertions ) { println!( "byte: {:02?}, width: {:02?}\n", line.len(), width.0 ) }
+	pub fn last_line( &self ) -> Option< LastLine > { self.last_line }
 
 
-		let line_width = width ;
+	// When the last line of a paragraph comes out shorter than this, the line before it is broken earlier if possible, so that
+	// some more text ends up on the last line.
+	//
+	pub fn set_last_line( &mut self, last_line: Option< LastLine > )
+	{
+		self.last_line = last_line;
+	}
 
-		if line_width.0 <= self.width { return Ok( line.to_string() ) }
 
-		let mut splits: Vec< SplitPoint > = Vec::with_capacity( line_width.0 );
+	pub fn balanced( &self ) -> bool { self.balanced }
 
 
-		// Harvest the split points from the generators
-		//
-		for generator in &self.generators
+	// In balanced mode, the text is wrapped to the smallest width that still gives the same number of lines as the width
+	// of the wrapper, so all lines come out about the same width. This is meant for headings, labels and the like.
+	//
+	pub fn set_balanced( &mut self, balanced: bool )
+	{
+		self.balanced = balanced;
+	}
+
+
+	pub fn observer( &self ) -> Option< &Observe > { self.observer.as_ref().map( |o| &**o ) }
+
+
+	// Let an observer follow what the wrapper does, eg. trace::Print. Nothing is reported without one.
+	//
+	pub fn set_observer( &mut self, observer: Option< Box<Observe> > )
+	{
+		self.observer = observer;
+	}
+
+
+	fn trace( &self, event: Event )
+	{
+		if let Some( ref observer ) = self.observer { observer.event( &event ) }
+	}
+
+
+	pub fn wrap( &self, line: &str ) -> Result< String, &'static str >
+	{
+		self.wrap_prepared( &self.prepare( line ), self.width )
+	}
+
+
+	// Wrap a text that was prepared before, to the given width. The result is the same as calling wrap with the wrapper
+	// set to that width, but the generators and filters don't run again, so this is cheap to call for many widths.
+	//
+	pub fn wrap_prepared( &self, prepared: &Prepared, width: usize ) -> Result< String, &'static str >
+	{
+		if width == 0 { return Err( "Wrapper.width cannot be zero" ) }
+
+		let ( _, cuts ) = self.cuts( prepared, width )?;
+
+		Ok( self.render( prepared, &cuts ) )
+	}
+
+
+	// Undo a previous wrap: join the lines of every paragraph, so the text can be wrapped again. Blank lines separate
+	// paragraphs and are kept as they are.
+	//
+	// For every line break, the generators are asked in turn whether they could have made it (see Generate::rejoin). The
+	// first one that answers decides how the lines are joined, so put the more specific generators, like a Hyphenator, before
+	// Xi. If none does, the line break stays.
+	//
+	pub fn unwrap( &self, text: &str ) -> String
+	{
+		let mut out   = String::with_capacity( text.len() );
+		let mut prev  = "";
+		let mut start = 0;
+		let mut brk   = "\n";
+
+		for raw in text.split( '\n' )
 		{
-			// TODO: shouldn't generator return an iterator rather than a vector?
+			// A line break may be "\r\n", the '\r' isn't part of the line.
 			//
-			for mut split in generator.opportunities( line )
+			let ( line, end ) = match raw.ends_with( '\r' )  &&  start + raw.len() < text.len()
+			{
+				true  => ( &raw[ ..raw.len() - 1 ], "\r\n" ),
+				false => ( raw                    , "\n"   ),
+			};
+
+			if start != 0
 			{
-				split.width = Some( split.start.to_width( &b2w ) + self.ruler.measure( &split.glue ) );
+				let joint = if prev.trim().is_empty() || line.trim().is_empty() { None } else
+				{
+					self.generators.iter().filter_map( |g| g.rejoin( &out, &text[ start.. ] ) ).next()
+				};
+
+				match joint
+				{
+					Some(( glue, joint )) =>
+					{
+						let len = out.len() - glue;
 
-				splits.push( split );
+						out.truncate( len );
+						out.push_str( &joint );
+					}
+
+					None => out.push_str( brk ),
+				}
 			}
-		}
 
+			out.push_str( line );
 
-		// Let filters do their work on the splits
-		//
-		for filter in &self.filters
-		{
-			filter.run( line, &mut splits );
+			prev   = line;
+			brk    = end;
+			start += raw.len() + 1;
 		}
 
+		out
+	}
 
-		// Sort the split points
-		// SplitPoints will be sorted on a score calculated by adding the start offset in bytes to the priority.
-		// This means that for a certain set of splitpoints, for which width + glue.width are within desired width, the
-		// splitpoint with the highest score will appear last in the vector.
-		//
-		splits.sort();
 
-		if cfg!( debug_assertions )
+	// Instead of breaking lines, mark where they may be broken, for when something else does the layout, like a browser.
+	// Splits that show glue when used, like those of a Hyphenator, get the hyphen marker, typically SOFT_HYPHEN. Other
+	// splits that don't consume whitespace, like the one Xi gives between ideographs, get the other marker, typically
+	// ZERO_WIDTH_SPACE or WBR. Whitespace and mandatory breaks are left alone, but the unbroken text of splits is applied.
+	// Whitespace of disabled splits becomes no-break spaces, so that the browser won't break there either.
+	//
+	pub fn annotate( &self, text: &str, hyphen: &str, other: &str ) -> String
+	{
+		let prepared = self.prepare( text );
+
+		let mut edits: Vec< ( usize, usize, &str ) > = Vec::with_capacity( prepared.splits.len() );
+
+		let enabled: HashSet<( usize, usize )> = prepared.splits.iter()
+
+			.filter( |s| s.enabled )
+			.map   ( |s| ( s.start.0, s.end.0 ) )
+			.collect()
+		;
+
+		for split in &prepared.splits
 		{
-			println!( "Available splits:" );
+			let span = &text[ split.start.0..split.end.0 ];
 
-			for split in & splits
+			// Eg. "Dr. Smith" with Binding. Unless another split still allows a break there.
+			//
+			if  !split.enabled  &&  !split.mandatory  &&  split.unbroken.is_none()  &&  !span.is_empty()
+			&&  span.chars().all( |c| c.is_whitespace()  &&  !util::char_is_line_break( &c ) )
+			&&  !enabled.contains( &( split.start.0, split.end.0 ) )
 			{
-				println!( "start: {:?}, end: {:?}", split.start.0, split.end.0 );
+				for ( i, c ) in span.char_indices()
+				{
+					let at = split.start.0 + i;
+					edits.push(( at, at + c.len_utf8(), "\u{A0}" ));
+				}
+
+				continue;
 			}
 
-			println!("");
-		}
+			let visible = !split.glue.trim_right_matches( |c| c == '\n' || c == '\r' ).is_empty();
+			let marker  = if visible { hyphen } else { other };
+			let usable  = split.enabled  &&  !split.mandatory  &&  split.start.0 != 0  &&  split.start.0 != text.len();
 
+			// Splits that only remove an invisible character, like a soft hyphen in the text, become markers too.
+			//
+			if usable  &&  split.unbroken.as_ref().map_or( split.start == split.end, |u| u.is_empty() )
+			{
+				edits.push(( split.start.0, split.end.0, marker ));
+			}
 
-		// Choose which split points we will actually use
-		//
-		// The offset where the current line starts in display width
-		//
-		let mut width_offset = WidthOffset( 0 );
+			else if let Some( ref unbroken ) = split.unbroken
+			{
+				edits.push(( split.start.0, split.end.0, unbroken.as_str() ));
+			}
+		}
 
-		// This is the index of the first split after we found one. This avoids re-considering splits several times.
-		//
-		let mut candidate    = 0;
 
-		// The actual split points that will be used to produce the return value.
-		// We probably won't be able to cut at ideal widths, so we might need an extra line, so plus one.
+		// One marker per offset. Sort other markers first, since if we may break without a hyphen, we don't want one.
 		//
-		let mut cuts       : Vec< &SplitPoint > = Vec::with_capacity( line_width.0 / self.width + 1 );
-		let mut extra_splits: Vec<  SplitPoint > = Vec::with_capacity( line_width.0 / self.width + 1 );
+		edits.sort_by_key( |edit| ( edit.0, edit.1, edit.2 != other ) );
+		edits.dedup_by_key( |edit| ( edit.0, edit.1 ) );
 
 
-		loop
+		let mut out   = String::with_capacity( text.len() + edits.len() * other.len() );
+		let mut start = 0;
+
+		for ( from, to, replacement ) in edits
 		{
-			// Do not search for a split point if the rest of the string fits in the current line.
+			// Overlapping edits, eg. Xi for mixed tabs and spaces. The first one wins.
 			//
-			let endl = width_offset + self.width;
+			if from < start { continue }
 
-			// If what we have left fits in one line, we are done.
-			//
-			if endl >= line_width { break }
+			out.push_str( &text[ start..from ] );
+			out.push_str( replacement );
+
+			start = to;
+		}
+
+		out.push_str( &text[ start.. ] );
+
+		out
+	}
+
+
+	// Wrap the text and tell for every line which other splits we could have cut it at, and why we didn't. A split is only
+	// listed with the first line it could have ended: the ones that fit and the first one that doesn't. In balanced mode, that
+	// is at the width balancing chose, which is what Explanation::width tells.
+	//
+	pub fn explain( &self, line: &str ) -> Result< Explanation, &'static str >
+	{
+		let prepared        = self.prepare( line );
+		let ( width, cuts ) = self.cuts( &prepared, self.width )?;
+		let wrapped         = self.lines( &prepared, &cuts );
+
+		let same = |a: &SplitPoint, b: &SplitPoint| a.start == b.start  &&  a.end == b.end  &&  a.glue == b.glue;
+
+		// The cuts before runts moved any, to tell which ones it moved.
+		//
+		let greedy = if self.last_line.is_some() { self.greedy( &prepared, width )? } else { cuts.clone() };
 
-			if cfg!( debug_assertions ) { println!("width_offset: {:?}, endl: {:?}, line_width: {:?}", width_offset.0, endl.0, line_width.0 ) }
+		let kept      = |c: &&SplitPoint| c.start.0 != 0  ||  c.mandatory;
+		let mut cuts  = cuts.iter().filter( &kept ).zip( greedy.iter().filter( &kept ) );
+		let mut lines = Vec::with_capacity( wrapped.len() );
+
+		// How many lines in a row ended in a split from the generator of the ladder, like choose counts them.
+		//
+		let mut consecutive = 0;
+
+		for line in &wrapped
+		{
+			let ( cut, moved ) = match cuts.next()
+			{
+				Some(( cut, greedy )) => ( cut, !same( cut, greedy ) ),
+
+				None =>
+				{
+					lines.push( Explained{ line: line.clone(), cut: None, rejected: Vec::new() } );
+					break;
+				}
+			};
 
 
-			let mut found: Option< &SplitPoint > = None             ;
-			let mut last_score                   = WidthOffset( 0 ) ;
+			let endl     = line.start.to_width( &prepared.b2w ) + width;
+			let widest   = line.start.to_width( &prepared.b2w ) + self.width;
+			let winner   = cut.score( &self.ruler );
+			let mut rejected = Vec::new();
 
-			// Figure out the last valid split point for each priority for this line.
+			// A split that fits and scores better is avoided either for the ladder, or because the line after this one ends
+			// its paragraph and would come out too short, see runts. Otherwise find never got to it.
 			//
-			for (i, split) in splits[ candidate.. ].iter().enumerate()
+			let ladder = match self.ladder
 			{
-				if cfg!( debug_assertions ) { println!( "Considering: start: {:?}, end: {:?} with endl: {:?}, score: {:?}", split.start.0, split.end.0, endl.0, split.score( &self.ruler ) ) }
+				Some(( ref generator, max )) if consecutive >= max => Some( generator.as_str() ),
+				_                                                  => None                      ,
+			};
 
+			let mut candidates: Vec< &SplitPoint > = prepared.splits.iter()
 
-				if split.width.unwrap() <= endl
+				.filter( |s| s.start > line.start  &&  !ends_text( &prepared.text, s )  &&  !same( s, cut ) )
+				.collect()
+			;
+
+			candidates.sort_by_key( |s| ( s.start, s.end ) );
+
+
+			for split in candidates
+			{
+				// Only the first split that doesn't fit any more, and nothing past a mandatory break.
+				//
+				if split.width.unwrap() > endl
 				{
-					if !split.enabled { continue }
+					let over = split.width.unwrap() - endl;
 
-					else if split.mandatory
+					if !cut.mandatory
 					{
-						found = Some( split );
-						candidate += i + 1   ;
-						break;
-					}
+						let reason = if split.width.unwrap() <= widest { Reason::Balanced( over ) } else { Reason::Overflow( over ) };
 
-					else if split.score( &self.ruler ) >= last_score
-					{
-						found      = Some( split ) ;
-						last_score = split.score( &self.ruler ) ;
+						rejected.push(( split.clone(), reason ));
 					}
 
-					else { continue }
+					break;
 				}
 
-				else
+				if cut.mandatory  &&  split.start >= cut.start { break }
+
+
+				let reason = if !split.enabled
 				{
-					candidate += i;
-					break
+					Reason::Disabled( split.filters.last().cloned() )
 				}
+
+				else if cut.mandatory                              { Reason::Mandatory                                                      }
+				else if split.score( &self.ruler ) <= winner       { Reason::Outscored{ score: split.score( &self.ruler ), winner: winner } }
+				else if ladder == Some( split.generator.as_str() ) { Reason::Ladder                                                         }
+				else if moved                                      { Reason::LastLine                                                       }
+				else                                               { Reason::NotReached                                                     }
+				;
+
+				rejected.push(( split.clone(), reason ));
 			}
 
 
-			if found.is_some()
+			consecutive = match self.ladder
 			{
-				let split = found.unwrap();
+				Some(( ref generator, _ )) if *generator == cut.generator => consecutive + 1,
+				_                                                         => 0              ,
+			};
 
-				if cfg!( debug_assertions ) { println!( "Found: {:?}, {:?}", split.start, split.end ); }
+			lines.push( Explained{ line: line.clone(), cut: Some( cut.clone() ), rejected: rejected } );
+		}
 
-				width_offset = split.end.to_width( &b2w );
-				cuts.push( split )
-			}
 
+		Ok( Explanation{ width: width, lines: lines } )
+	}
 
-			// We found none, but we can cut off words if we have to
-			// I don't like this implementation. It greatly complexifies the code compared to before break_word.
-			// However I haven't found any way of expressing this in rust.
-			// Another option would be to copy SplitPoints and letting cuts have their own copy. That would keep code clean.
-			// However that means extra copying instead of just keeping a reference.
-			//
-			else if self.break_word
-			{
-				let offset = endl.to_bytes( &w2b );
-				let mut split = SplitPoint::new( offset.0, offset.0, 0 );
-				split.width = Some( endl + self.ruler.measure( &self.glue ) );
 
-				width_offset = endl;
-				extra_splits.push( split );
+	// Wrap text that was wrapped before, possibly to another width. See unwrap.
+	//
+	pub fn refill( &self, text: &str ) -> Result< String, &'static str >
+	{
+		self.wrap( &self.unwrap( text ) )
+	}
+
+
+	// The narrowest width the text can be wrapped to, which is the width of the widest part of the text that has no enabled
+	// split points, including the glue of the split that ends it. With break_word, this is the widest grapheme cluster.
+	//
+	pub fn min_width( &self, line: &str ) -> usize
+	{
+		self.min_width_prepared( &self.prepare( line ) )
+	}
+
+
+	// The width of the text when it's not wrapped, that is the widest line between mandatory splits.
+	//
+	pub fn max_width( &self, line: &str ) -> usize
+	{
+		self.max_width_prepared( &self.prepare( line ) )
+	}
+
+
+	// The smallest width at which the text wraps to at most the given number of lines, with the wrapped text.
+	// The generators and filters only run once for all the widths we try.
+	//
+	pub fn fit( &self, line: &str, lines: usize ) -> Result< ( usize, String ), &'static str >
+	{
+		self.fit_prepared( &self.prepare( line ), lines )
+	}
+
+
+	pub fn fit_prepared( &self, prepared: &Prepared, lines: usize ) -> Result< ( usize, String ), &'static str >
+	{
+		if lines == 0 { return Err( "Cannot fit text in zero lines" ) }
+
+		let low  = std::cmp::max( 1, self.min_width_prepared( prepared ) );
+		let high = std::cmp::max( 1, self.max_width_prepared( prepared ) );
+
+		match self.narrowest( prepared, low, high, lines, |_| true )
+		{
+			Some(( width, cuts )) => Ok(( width, self.render( prepared, &cuts ) )),
+			None                  => Err( "Mandatory breaks give more lines than requested" ),
+		}
+	}
+
+
+	pub fn min_width_prepared( &self, prepared: &Prepared ) -> usize
+	{
+		if self.break_word
+		{
+			let glue = self.ruler.measure( &self.glue );
+
+			return prepared.text.graphemes( true ).map( |g| self.ruler.measure( g ) + glue ).max().unwrap_or( 0 )
+		}
+
+		widest( prepared, |split| split.enabled )
+	}
+
+
+	pub fn max_width_prepared( &self, prepared: &Prepared ) -> usize
+	{
+		widest( prepared, |split| split.enabled && split.mandatory )
+	}
+
+
+	// Measure the text and harvest the split points. None of this depends on the width we wrap to, so the result can be
+	// wrapped to many widths with wrap_prepared.
+	//
+	pub fn prepare( &self, line: &str ) -> Prepared
+	{
+		// store byte to width conversion, because we will need to calculate our breakpoint in terms of display width.
+		//
+		let mut b2w: HashMap < ByteOffset , WidthOffset > = HashMap::with_capacity( line.len() );
+		let mut w2b: HashMap < WidthOffset, ByteOffset  > = HashMap::with_capacity( line.len() );
+
+		// We don't return early when the line fits, since splits might still want to replace some text (see SplitPoint.unbroken).
+		//
+		let mut splits = self.harvest( line, ByteOffset( 0 ) );
+
+		let width = self.measure( line, ByteOffset( 0 ), WidthOffset( 0 ), &splits, &mut b2w, &mut w2b );
+
+		self.place( &mut splits, &b2w );
+
+
+		// Sort the split points
+		// SplitPoints will be sorted on a score calculated by adding the start offset in bytes to the priority.
+		// This means that for a certain set of splitpoints, for which width + glue.width are within desired width, the
+		// splitpoint with the highest score will appear last in the vector.
+		//
+		splits.sort();
+
+
+		Prepared
+		{
+			text  : line.to_string() ,
+			b2w   : b2w              ,
+			w2b   : w2b              ,
+			width : width            ,
+			splits: splits           ,
+		}
+	}
+
+
+	// Store the width at every grapheme boundary of text, which starts at the given offsets in the complete text.
+	// Returns the width at the end of text.
+	//
+	// Text that splits replace when we don't cut at them (see SplitPoint.unbroken) counts as its replacement, since that's
+	// what ends up on the line. Offsets within it have the width where the replacement starts, like the columns of a Line.
+	//
+	fn measure
+	(
+		&self                                             ,
+		text  : &str                                      ,
+		bytes : ByteOffset                                ,
+		width : WidthOffset                               ,
+		splits: &[ SplitPoint ]                           ,
+		b2w   : &mut HashMap < ByteOffset , WidthOffset > ,
+		w2b   : &mut HashMap < WidthOffset, ByteOffset  > ,
+	)
+
+	-> WidthOffset
+	{
+		let mut replaced: Vec< &SplitPoint > = splits.iter().filter( |s| s.unbroken.is_some() ).collect();
+
+		replaced.sort_by_key( |s| s.start );
+
+		let mut replaced = replaced.into_iter().peekable();
+		let mut width    = width;
+		let mut within   = None; // The end of the replaced text we are in and the width where its replacement starts.
+
+		for ( offset, graph ) in text.grapheme_indices( true )
+		{
+			let at = bytes + offset;
+
+			match within
+			{
+				Some(( end, start )) if at < end => { b2w.insert( at, start ); continue }
+				_                                => within = None,
 			}
 
+			b2w.insert( at   , width );
+			w2b.insert( width, at    );
 
-			else
+			self.trace( Event::Grapheme{ offset: at, width: width, grapheme: graph } );
+
+
+			// Overlapping splits, eg. Xi for mixed tabs and spaces. The first one wins.
+			//
+			while replaced.peek().map_or( false, |s| s.start < at ) { replaced.next(); }
+
+			if replaced.peek().map_or( false, |s| s.start == at )
 			{
-				return Err( "No valid split point found" );
+				let split = replaced.next().unwrap();
+
+				within = Some(( split.end, width ));
+				width  = width + self.ruler.measure( split.unbroken.as_ref().unwrap() );
+
+				if split.end > at { continue }
 			}
+
+			width = width + self.ruler.measure( graph );
 		}
 
+		// Add the end of the string
+		//
+		b2w.insert( bytes + text.len(), width              );
+		w2b.insert( width             , bytes + text.len() );
+
+		width
+	}
 
-		if cfg!( debug_assertions )
+
+	// Set the width of the split points, which is where they start plus their glue.
+	//
+	fn place( &self, splits: &mut [ SplitPoint ], b2w: &HashMap < ByteOffset, WidthOffset > )
+	{
+		for split in splits
 		{
-			for c in &cuts
-			{
-				println!( "{:?}", c );
-			}
-			println!("");
+			split.width = Some( split.start.to_width( b2w ) + self.ruler.measure( &split.glue ) );
 		}
+	}
+
+
+	// Run the generators and filters on text, which starts at offset in the complete text. The split points we return have
+	// offsets in the complete text, but they aren't measured nor sorted yet.
+	//
+	fn harvest( &self, text: &str, offset: ByteOffset ) -> Vec< SplitPoint >
+	{
+		let mut splits: Vec< SplitPoint > = Vec::with_capacity( text.len() );
+
+
+		// Harvest the split points from the generators
+		//
+		for ( i, generator ) in self.generators.iter().enumerate()
+		{
+			// TODO: shouldn't generator return an iterator rather than a vector?
+			//
+			let mut proposed = generator.opportunities( text );
+
+			for split in &mut proposed
+			{
+				// Generators that combine others may have named the splits already.
+				//
+				if split.generator.is_empty() { split.generator = generator.name().to_string() }
+
+				if self.observer.is_some() { self.trace( Event::Generated{ generator: i, split: &shift( split, offset ) } ) }
+			}
+
+			splits.extend( proposed );
+		}
+
+
+		// Let filters do their work on the splits. We compare the splits before and after to know which ones a filter changed.
+		// Filters may remove, add and reorder splits, so we don't go by index: a split is unchanged if an equal one was there
+		// before. We only keep hashes of the splits, unless the observer wants to see what a changed split looked like.
+		//
+		for ( i, filter ) in self.filters.iter().enumerate()
+		{
+			let mut before: HashMap< u64, usize > = HashMap::with_capacity( splits.len() );
+
+			for split in &splits { *before.entry( fingerprint( split ) ).or_insert( 0 ) += 1 }
+
+			let old: HashMap< ( ByteOffset, ByteOffset, String ), SplitPoint > = match self.observer
+			{
+				Some( _ ) => splits.iter().map( |s| ( ( s.start, s.end, s.glue.clone() ), shift( s, offset ) ) ).collect(),
+				None      => HashMap::new(),
+			};
+
+
+			filter.run( text, &mut splits );
+
+			for split in &mut splits
+			{
+				if let Some( count ) = before.get_mut( &fingerprint( split ) )
+				{
+					if *count > 0 { *count -= 1; continue }
+				}
+
+				split.filters.push( filter.name().to_string() );
+
+				if self.observer.is_none() { continue }
+
+				let was = old.get( &( split.start, split.end, split.glue.clone() ) );
+
+				self.trace( Event::Filtered{ filter: i, before: was, after: &shift( split, offset ) } );
+			}
+		}
+
+
+		// Move the splits to their place in the complete text, including the ones filters added.
+		//
+		for split in &mut splits
+		{
+			split.start = split.start + offset;
+			split.end   = split.end   + offset;
+		}
+
+		splits
+	}
+
+
+	// Wrap the text and keep what we need to update it after an edit.
+	//
+	pub fn layout( &self, line: &str ) -> Result< Layout, &'static str >
+	{
+		self.layout_prepared( self.prepare( line ), self.width )
+	}
+
+
+	pub fn layout_prepared( &self, prepared: Prepared, width: usize ) -> Result< Layout, &'static str >
+	{
+		if width == 0 { return Err( "Wrapper.width cannot be zero" ) }
+
+		let ( _, cuts ) = self.cuts( &prepared, width )?;
+		let lines       = self.lines( &prepared, &cuts );
+
+		Ok( Layout
+		{
+			prepared: prepared ,
+			width   : width    ,
+			cuts    : cuts     ,
+			lines   : lines    ,
+		})
+	}
+
+
+	// Replace the bytes in range by text and rewrap. Only the paragraphs touched by the edit go through the generators and
+	// filters again, the split points of the rest of the text are kept. This relies on generators and filters not looking
+	// past mandatory breaks. Those that do say so with Generate::local or Filter::local, eg. Restrict, Spans and the Pattern
+	// filter, and then the whole text is harvested again. Choosing the cuts is cheap next to that, so it's done for the whole text again, which also takes
+	// care of balanced mode.
+	//
+	// Returns the range of lines in the new layout that differ from the old one. Lines before it are unchanged, and so are
+	// the lines after it, albeit they may have moved.
+	//
+	pub fn edit( &self, layout: &mut Layout, range: Range< usize >, text: &str ) -> Result< Range< usize >, &'static str >
+	{
+		let old = &layout.prepared;
+
+		if range.start > range.end
+		|| range.end   > old.text.len()
+		|| !old.text.is_char_boundary( range.start )
+		|| !old.text.is_char_boundary( range.end   )
+		{
+			return Err( "Edit range is not within the text" );
+		}
+
+
+		// The paragraph runs from the end of the last mandatory split before the edit, up to and including the first mandatory
+		// split after it. That split is kept as is. Line breaks that touch the edit aren't kept, since the edit may change
+		// them, eg. a "\r" before a "\n" makes one line break of two.
+		//
+		// When there is none after it, the paragraph runs to the end of the text. Then the line break before the edit may end
+		// up at the end of the text, where it gets the split for the end of the text instead of its own, so we take the paragraph
+		// before it along.
+		//
+		// When a generator or filter isn't local, the paragraph is the whole text.
+		//
+		let local     = self.generators.iter().all( |g| g.local() )  &&  self.filters.iter().all( |f| f.local() );
+		let mandatory = || old.splits.iter().filter( move |s| local && s.mandatory && !ends_text( &old.text, s ) );
+
+		let next  = mandatory().filter( |s| s.start.0 > range.end ).min_by_key( |s| s.start );
+
+		let mut ends: Vec< ByteOffset > = mandatory().filter( |s| s.end.0 < range.start ).map( |s| s.end ).collect();
+
+		ends.sort();
+
+		if next.is_none() { ends.pop(); }
+
+		let start = ends.last().cloned().unwrap_or( ByteOffset( 0 ) );
+
+		let ( end, stop ) = match next
+		{
+			Some( split ) => ( split.start, split.end                   ),
+			None          => ( ByteOffset( old.text.len() ), ByteOffset( old.text.len() ) ),
+		};
+
+
+		let mut new = String::with_capacity( old.text.len() + text.len() );
+
+		new.push_str( &old.text[ ..range.start ] );
+		new.push_str( text                        );
+		new.push_str( &old.text[ range.end..   ] );
+
+		let delta = |offset: ByteOffset| offset + text.len() - ( range.end - range.start );
+
+
+		// Widths before the paragraph stay, the ones after it shift by the difference in width of the paragraph.
+		//
+		let mut b2w: HashMap < ByteOffset , WidthOffset > = HashMap::with_capacity( new.len() );
+		let mut w2b: HashMap < WidthOffset, ByteOffset  > = HashMap::with_capacity( new.len() );
+
+		let from   = start.to_width( &old.b2w );
+		let old_to = stop .to_width( &old.b2w );
+
+		b2w.extend( old.b2w.iter().filter( |&( b, _ )| *b < start ).map( |( &b, &w )| ( b, w ) ) );
+		w2b.extend( old.w2b.iter().filter( |&( _, b )| *b < start ).map( |( &w, &b )| ( w, b ) ) );
+
+		let paragraph     = &new[ start.0..delta( stop ).0 ];
+		let mut harvested = self.harvest( paragraph, start );
+
+		let to = self.measure( paragraph, start, from, &harvested, &mut b2w, &mut w2b );
+
+		let shift = |width: WidthOffset| width + to.0 - old_to.0;
+
+		b2w.extend( old.b2w.iter().filter( |&( b, _ )| *b > stop ).map( |( &b, &w )| ( delta( b ), shift( w ) ) ) );
+		w2b.extend( old.w2b.iter().filter( |&( _, b )| *b > stop ).map( |( &w, &b )| ( shift( w ), delta( b ) ) ) );
+
+
+		// Split points before the paragraph stay, the ones from the mandatory split after it on shift. The eot split of the
+		// paragraph only counts when it's the end of the text.
+		//
+		let last = delta( end );
+		let eot  = stop.0 == old.text.len();
+
+		let mut splits: Vec< SplitPoint > = old.splits.iter().filter( |s| s.start < start ).cloned().collect();
+
+		self.place( &mut harvested, &b2w );
+
+		splits.extend( harvested.into_iter().filter( |s| eot || s.start < last ) );
+
+		for split in old.splits.iter().filter( |s| s.start >= end && !( eot && s.start.0 == old.text.len() ) )
+		{
+			let mut split = split.clone();
+
+			split.start = delta( split.start );
+			split.end   = delta( split.end   );
+			split.width = Some( split.start.to_width( &b2w ) + self.ruler.measure( &split.glue ) );
+
+			splits.push( split );
+		}
+
+		splits.sort();
+
+
+		let width    = b2w[ &ByteOffset( new.len() ) ];
+		let prepared = Prepared{ text: new, b2w: b2w, w2b: w2b, width: width, splits: splits };
+		let updated  = self.layout_prepared( prepared, layout.width )?;
+
+
+		// Compare the lines to find what changed. Lines are the same if they only moved by what the edit inserted and removed,
+		// which is nothing for the lines before it, so compare their offsets with that taken into account.
+		//
+		let same = |a: &Line, b: &Line, inserted: usize, removed: usize|
+		{
+			let moved = |x: ByteOffset, y: ByteOffset| x.0 + inserted == y.0 + removed;
+
+			   a.text == b.text  &&  a.glue == b.glue
+			&& moved( a.start, b.start )  &&  moved( a.end, b.end )
+			&& a.columns.len() == b.columns.len()
+			&& a.columns.iter().zip( &b.columns ).all( |( x, y )| moved( x.0, y.0 )  &&  x.1 == y.1 )
+		};
+
+		let prefix = layout.lines.iter().zip( updated.lines.iter() )
+
+			.take_while( |&( a, b )| same( a, b, 0, 0 ) )
+			.count()
+		;
+
+		let suffix = layout.lines.iter().rev().zip( updated.lines.iter().rev() )
+
+			.take( std::cmp::min( layout.lines.len(), updated.lines.len() ) - prefix )
+			.take_while( |&( a, b )| same( a, b, text.len(), range.end - range.start ) )
+			.count()
+		;
+
+		let changed = prefix..updated.lines.len() - suffix;
+
+		*layout = updated;
+
+		Ok( changed )
+	}
+
+
+	// The cuts for the text at the given width, balanced if the wrapper says so. Returns the width the cuts were chosen at
+	// as well, which is narrower than width when balancing.
+	//
+	fn cuts( &self, prepared: &Prepared, width: usize ) -> Result< ( usize, Vec< SplitPoint > ), &'static str >
+	{
+		let cuts = self.choose( prepared, width )?;
+
+		if self.balanced { Ok( self.balance( prepared, width, cuts ) ) } else { Ok(( width, cuts )) }
+	}
+
+
+	// Choose which split points we will actually use to fit the text in the given width.
+	//
+	fn choose( &self, prepared: &Prepared, width: usize ) -> Result< Vec< SplitPoint >, &'static str >
+	{
+		let mut cuts = self.greedy( prepared, width )?;
+
+		if let Some( last_line ) = self.last_line
+		{
+			self.runts( prepared, width, last_line, &mut cuts );
+		}
+
+		Ok( cuts )
+	}
+
+
+	// Fill every line as well as find can, without looking at the last lines of the paragraphs yet, see runts.
+	//
+	fn greedy( &self, prepared: &Prepared, width: usize ) -> Result< Vec< SplitPoint >, &'static str >
+	{
+		let line_width = prepared.width;
+
+		// The offset where the current line starts in display width
+		//
+		let mut width_offset = WidthOffset( 0 );
+
+		// This is the index of the first split after we found one. This avoids re-considering splits several times.
+		//
+		let mut candidate    = 0;
+
+		// How many lines in a row ended in a split from the generator of the ladder.
+		//
+		let mut consecutive  = 0;
+
+		// The actual split points that will be used to produce the return value.
+		// We probably won't be able to cut at ideal widths, so we might need an extra line, so plus one.
+		//
+		let mut cuts: Vec< SplitPoint > = Vec::with_capacity( line_width.0 / width + 1 );
+
+
+		loop
+		{
+			// Do not search for a split point if the rest of the string fits in the current line.
+			//
+			let endl = width_offset + width;
+
+			// Where the current line starts in bytes.
+			//
+			let from = cuts.last().map_or( ByteOffset( 0 ), |c| c.end );
+
+			// If what we have left fits in one line, we are done, but for the mandatory breaks in it.
+			//
+			if endl >= line_width
+			{
+				let mut rest: Vec< &SplitPoint > = prepared.splits.iter()
+
+					.filter( |s| s.mandatory  &&  s.enabled  &&  s.start >= from  &&  !ends_text( &prepared.text, s ) )
+					.filter( |s| cuts.last().map_or( true, |c| c.start != s.start ) )
+					.collect()
+				;
+
+				rest.sort_by_key( |s| s.start );
+				cuts.extend( rest.into_iter().cloned() );
+
+				break;
+			}
+
+			self.trace( Event::Line{ start: width_offset, end: endl } );
+
+
+			let avoid = match self.ladder
+			{
+				Some(( ref generator, max )) if consecutive >= max => Some( generator.as_str() ),
+				_                                                  => None                      ,
+			};
+
+			let ( mut found, mut next ) = self.find( &prepared.splits, candidate, from, endl, avoid );
+
+			if found.is_none() && avoid.is_some()
+			{
+				let retry = self.find( &prepared.splits, candidate, from, endl, None );
+
+				found = retry.0;
+				next  = retry.1;
+			}
+
+			candidate = next;
+
+
+			if let Some( split ) = found
+			{
+				self.trace( Event::Chosen{ split: split } );
+
+				consecutive = match self.ladder
+				{
+					Some(( ref generator, _ )) if *generator == split.generator => consecutive + 1,
+					_                                                           => 0              ,
+				};
+
+				width_offset = split.end.to_width( &prepared.b2w );
+				cuts.push( split.clone() )
+			}
+
+
+			// We found none, but we can cut off words if we have to
+			//
+			else if self.break_word
+			{
+				let offset = endl.to_bytes( &prepared.w2b );
+				let mut split = SplitPoint::new( offset.0, offset.0, 0 );
+				split.width = Some( endl + self.ruler.measure( &self.glue ) );
+				split.glue  = self.glue.clone();
+
+				self.trace( Event::BrokenWord{ split: &split } );
+
+				consecutive  = 0;
+				width_offset = endl;
+				cuts.push( split );
+			}
+
+
+			else
+			{
+				return Err( "No valid split point found" );
+			}
+		}
+
+		Ok( cuts )
+	}
+
+
+	// Find the smallest width that doesn't give more lines than the cuts we got at the width of the wrapper. With break_word,
+	// it also may not break more words, or a word that fits on a line would be cut just to make the lines even.
+	//
+	fn balance( &self, prepared: &Prepared, width: usize, cuts: Vec< SplitPoint > ) -> ( usize, Vec< SplitPoint > )
+	{
+		let lines = count_lines( prepared, &cuts );
+
+		if lines <= 1 { return ( width, cuts ) }
+
+		let splits: HashSet< ( ByteOffset, ByteOffset ) > = prepared.splits.iter().map( |s| ( s.start, s.end ) ).collect();
+
+		let broken = |cuts: &[SplitPoint]| cuts.iter().filter( |c| !splits.contains( &( c.start, c.end ) ) ).count();
+		let most   = broken( &cuts );
+
+		self.narrowest( prepared, 1, width, lines, |cuts| broken( cuts ) <= most ).unwrap_or(( width, cuts ))
+	}
+
+
+	// Binary search for the smallest width between low and high at which the text wraps to at most the given amount of lines,
+	// with cuts that accept agrees with. Returns that width and the cuts, or None if even high doesn't do.
+	//
+	// The ladder can make a width fail where a narrower one fits, when it keeps a line from ending in the only split that would
+	// do. The binary search may then step over the width we look for, so with a ladder we try every width below the one it
+	// found, from the narrowest up.
+	//
+	fn narrowest< A >( &self, prepared: &Prepared, low: usize, high: usize, lines: usize, accept: A ) -> Option<( usize, Vec< SplitPoint > )>
+
+		where A: Fn( &[SplitPoint] ) -> bool
+	{
+		let fits = |width: usize| match self.choose( prepared, width )
+		{
+			Ok( cuts ) => if count_lines( prepared, &cuts ) <= lines  &&  accept( &cuts ) { Some( cuts ) } else { None },
+			_          => None,
+		};
+
+		let     floor = low ;
+		let mut low   = low ;
+		let mut high  = high;
+
+		while low < high
+		{
+			let mid = ( low + high ) / 2;
+
+			if fits( mid ).is_some() { high = mid } else { low = mid + 1 }
+		}
+
+
+		// The amount of lines doesn't strictly grow when the width shrinks, so verify.
+		//
+		let found = match fits( low )
+		{
+			Some( cuts ) => ( low, cuts ),
+			None         => return None  ,
+		};
+
+		if self.ladder.is_none() { return Some( found ) }
+
+		( floor..found.0 ).filter_map( |width| fits( width ).map( |cuts| ( width, cuts ) ) ).next().or( Some( found ) )
+	}
+
+
+	// The greedy algorithm in layout never revisits a split, so we look at the last line of every paragraph afterwards. If it's
+	// too short, we move the cut of the line before to the latest split that leaves enough text on the last line, as long as
+	// all of it still fits.
+	//
+	fn runts( &self, prepared: &Prepared, width: usize, last_line: LastLine, cuts: &mut Vec< SplitPoint > )
+	{
+		let line = prepared.text.as_str();
+
+		// The ends of the paragraphs, as the index of the cut before the last line and the byte offset where the last line ends.
+		//
+		let mut ends: Vec< ( usize, ByteOffset ) > = Vec::new();
+
+		for ( i, cut ) in cuts.iter().enumerate()
+		{
+			if cut.mandatory && i > 0 && !cuts[ i - 1 ].mandatory { ends.push(( i - 1, cut.start )) }
+		}
+
+		if let Some( cut ) = cuts.last()
+		{
+			if !cut.mandatory { ends.push(( cuts.len() - 1, ByteOffset( line.len() ) )) }
+		}
+
+
+		let short = |from: ByteOffset, to: ByteOffset| match last_line
+		{
+			LastLine::Width( w ) => ( to.to_width( &prepared.b2w ) - from.to_width( &prepared.b2w ) ).0 < w,
+			LastLine::Words( n ) => line[ from.0..to.0 ].split_whitespace().count()                        < n,
+		};
+
+
+		for ( i, end ) in ends
+		{
+			if !short( cuts[ i ].end, end ) { continue }
+
+			let start = if i > 0 { cuts[ i - 1 ].end } else { ByteOffset( 0 ) };
+			let endl  = start.to_width( &prepared.b2w ) + width;
+
+			let better = prepared.splits.iter()
+
+				.filter( |s| s.enabled && !s.mandatory                                 )
+				.filter( |s| s.start > start && s.start < cuts[ i ].start              )
+				.filter( |s| s.width.unwrap() <= endl                                  )
+				.filter( |s| end.to_width( &prepared.b2w ) - s.end.to_width( &prepared.b2w ) <= WidthOffset( width ) )
+				.filter( |s| !short( s.end, end )                                      )
+				.max_by_key( |s| s.score( &self.ruler ) )
+			;
+
+			if let Some( split ) = better { cuts[ i ] = split.clone() }
+		}
+	}
+
+
+	// Figure out the last valid split point for each priority for the line ending at endl. Returns the split, if any, and
+	// the index to start looking from for the next line. Splits from the generator in avoid are skipped, unless they are
+	// mandatory.
+	// So are splits that start before from, where the line starts, which happens when we broke a word after text that takes
+	// no room, like a soft hyphen.
+	//
+	fn find<'a>( &self, splits: &'a [SplitPoint], candidate: usize, from: ByteOffset, endl: WidthOffset, avoid: Option<&str> )
+
+	-> ( Option< &'a SplitPoint >, usize )
+	{
+		let mut found: Option< &SplitPoint > = None             ;
+		let mut last_score                   = 0                ;
+		let mut chosen                       = 0                ;
+		let mut next                         = None             ;
+
+		for (i, split) in splits[ candidate.. ].iter().enumerate()
+		{
+			self.trace( Event::Considered{ split: split, score: split.score( &self.ruler ) } );
+
+
+			if split.width.unwrap() <= endl
+			{
+				if !split.enabled  ||  split.start < from { continue }
+
+				else if split.mandatory
+				{
+					found = Some( split );
+					next  = Some( candidate + i + 1 );
+					break;
+				}
+
+				else if avoid == Some( split.generator.as_str() ) { continue }
+
+				else if found.is_none()  ||  split.score( &self.ruler ) >= last_score
+				{
+					found      = Some( split ) ;
+					last_score = split.score( &self.ruler ) ;
+					chosen     = candidate + i ;
+				}
+
+				else { continue }
+			}
+
+			else
+			{
+				next = Some( candidate + i );
+				break
+			}
+		}
+
+
+		// If all the splits that are left fit, the next line starts after the one we chose, or we would choose it again.
+		//
+		let next = next.unwrap_or( if found.is_some() { chosen + 1 } else { candidate } );
+
+		( found, next )
+	}
+
+
+	// Apply the glue of the cuts and the unbroken text of the splits we didn't use.
+	//
+	fn render( &self, prepared: &Prepared, cuts: &[SplitPoint] ) -> String
+	{
+		let mut out = String::with_capacity( prepared.text.len() + cuts.len() * 2 );
+
+		for line in self.lines( prepared, cuts )
+		{
+			out.push_str( &line.text );
+			out.push_str( &line.glue );
+		}
+
+		out
+	}
+
+
+	// Cut the text in lines. Every line but the last ends in the glue of its cut, and the splits we don't use may replace the
+	// text they span if they have an unbroken value.
+	//
+	fn lines( &self, prepared: &Prepared, cuts: &[SplitPoint] ) -> Vec< Line >
+	{
+		let     text  = prepared.text.as_str();
+		let mut lines = Vec::with_capacity( cuts.len() + 1 );
+		let mut start = ByteOffset( 0 );
+
+		for cut in cuts
+		{
+			// We should never try to cut at the end of the string, but it happens.
+			// After some time, this can be commented out.
+			//
+			debug_assert!( cut.start.0 != text.len() );
+
+			// A cut at the start of the text only removes what it spans, and one at the end adds no glue, unless they are
+			// mandatory breaks in the text.
+			//
+			if cut.start.0 == 0  &&  !cut.mandatory { start = cut.end; continue }
+
+			let glue = if cut.end.0 != text.len()  ||  cut.mandatory { cut.glue.clone() } else { String::new() };
+
+			lines.push( self.line( prepared, start, cut.start, glue ) );
+
+			start = cut.end;
+		}
+
+
+		// Unless the last cut consumed the end of the text.
+		//
+		if lines.is_empty()  ||  start.0 != text.len()
+		{
+			lines.push( self.line( prepared, start, ByteOffset( text.len() ), String::new() ) );
+		}
+
+		lines
+	}
+
+
+	// The line of text between start and end, with the unbroken text of the splits within it.
+	//
+	fn line( &self, prepared: &Prepared, start: ByteOffset, end: ByteOffset, glue: String ) -> Line
+	{
+		let mut text    = String::with_capacity( end.0 - start.0 );
+		let mut columns = Vec::with_capacity( end.0 - start.0 + 1 );
+		let mut column  = WidthOffset( 0 );
+		let mut from    = start;
+
+		let mut replaced: Vec< &SplitPoint > = prepared.splits.iter()
+
+			.filter( |s| s.start >= start  &&  s.end <= end  &&  s.unbroken.is_some() )
+			.collect()
+		;
+
+		replaced.sort_by_key( |s| s.start );
+
+
+		// Copy the text from the source between from and to, noting the column of every grapheme boundary.
+		//
+		let copy = |text: &mut String, columns: &mut Vec<( ByteOffset, WidthOffset )>, from: ByteOffset, to: ByteOffset, column: &mut WidthOffset|
+		{
+			for ( offset, graph ) in prepared.text[ from.0..to.0 ].grapheme_indices( true )
+			{
+				columns.push(( from + offset, *column ));
+
+				*column = *column + self.ruler.measure( graph );
+			}
+
+			text.push_str( &prepared.text[ from.0..to.0 ] );
+		};
+
+
+		for split in replaced
+		{
+			// Overlapping splits, eg. Xi for mixed tabs and spaces. The first one wins.
+			//
+			if split.start < from { continue }
+
+			let unbroken = split.unbroken.as_ref().unwrap();
+
+			copy( &mut text, &mut columns, from, split.start, &mut column );
+
+			// The whole replaced range maps to the column where the replacement starts.
+			//
+			columns.push(( split.start, column ));
+
+			column = column + self.ruler.measure( unbroken );
+			text.push_str( unbroken );
+
+			from = split.end;
+		}
+
+		copy( &mut text, &mut columns, from, end, &mut column );
+		columns.push(( end, column ));
+
+
+		Line { start: start, end: end, text: text, glue: glue, columns: columns }
+	}
+}
+
+
+
+#[cfg(feature = "rayon")]
+//
+impl<Ruler> Wrapper<Ruler> where Ruler: TextWidth + Sync
+{
+	// Wrap many paragraphs in parallel on the global rayon thread pool. Every paragraph is wrapped on its own, like with
+	// wrap, and the results come in the same order as the paragraphs. An error for one paragraph doesn't stop the others.
+	//
+	pub fn wrap_paragraphs< S >( &self, paragraphs: &[S] ) -> Vec< Result< String, &'static str > >
+
+		where S: AsRef< str > + Sync
+	{
+		paragraphs.par_iter().map( |p| self.wrap( p.as_ref() ) ).collect()
+	}
+}
+
+
+
+// A copy of a split of a part of the text that starts at offset, with offsets in the complete text.
+//
+fn shift( split: &SplitPoint, offset: ByteOffset ) -> SplitPoint
+{
+	let mut split = split.clone();
+
+	split.start = split.start + offset;
+	split.end   = split.end   + offset;
+
+	split
+}
+
+
+
+// Tells whether a filter changed a split, without keeping a copy of it around.
+//
+fn fingerprint( split: &SplitPoint ) -> u64
+{
+	use std::hash::{ Hash, Hasher };
+
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+
+	split.hash( &mut hasher );
+	hasher.finish()
+}
+
+
+
+// The width of the widest line we get when cutting the text at every split point that passes the predicate.
+//
+fn widest< P >( prepared: &Prepared, predicate: P ) -> usize
+
+	where P: Fn( &SplitPoint ) -> bool
+{
+	let mut splits: Vec< &SplitPoint > = prepared.splits.iter().filter( |s| predicate( s ) ).collect();
+
+	splits.sort_by_key( |s| ( s.start, s.end ) );
+
+
+	let mut from   = ByteOffset( 0 );
+	let mut widest = WidthOffset( 0 );
+
+	for split in splits
+	{
+		// The end of the text is not a split.
+		//
+		if split.start.0 == prepared.text.len() { continue }
+
+		// Overlapping splits, like Xi gives for mixed tabs and spaces. The next line starts after the one that consumes most.
+		//
+		if split.start >= from
+		{
+			widest = std::cmp::max( widest, split.width.unwrap() - from.to_width( &prepared.b2w ) );
+		}
+
+		from = std::cmp::max( from, split.end );
+	}
+
+	std::cmp::max( widest, prepared.width - from.to_width( &prepared.b2w ) ).0
+}
+
+
+
+// The number of lines the text will have when cut at these cuts, see Wrapper::lines.
+//
+fn count_lines( prepared: &Prepared, cuts: &[SplitPoint] ) -> usize
+{
+	let ends = cuts.iter().filter( |cut| cut.start.0 != 0  ||  cut.mandatory ).count();
+
+	match cuts.last()
+	{
+		Some( cut ) if ends > 0  &&  cut.end.0 == prepared.text.len() => ends    ,
+		_                                                             => ends + 1,
+	}
+}
+
+
+
+// Whether a split is the one for the end of the text, rather than a line break in it. When the text ends in whitespace, Xi
+// starts it before the whitespace, so it isn't always at the end of the text.
+//
+fn ends_text( text: &str, split: &SplitPoint ) -> bool
+{
+	let rest = &text[ split.start.0.. ];
+
+	split.mandatory  &&  rest.trim().is_empty()  &&  !rest.chars().any( |c| util::char_is_line_break( &c ) )
+}
+
+
+
+// The result of measuring a text and running the generators and filters on it. See Wrapper::prepare.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Prepared
+{
+	text  : String                               ,
+	b2w   : HashMap < ByteOffset , WidthOffset > , // The display width at every grapheme boundary.
+	w2b   : HashMap < WidthOffset, ByteOffset  > ,
+	width : WidthOffset                          , // The width of the whole text.
+	splits: Vec< SplitPoint >                    , // Sorted, see SplitPoint::cmp.
+}
+
+
+
+impl Prepared
+{
+	pub fn text  ( &self ) -> &str            { &self.text   }
+	pub fn width ( &self ) -> WidthOffset     {  self.width  }
+	pub fn splits( &self ) -> &[ SplitPoint ] { &self.splits }
+}
+
+
+
+
+// A wrapped text that can be updated after edits with Wrapper::edit. See Wrapper::layout.
+//
+#[ derive( Clone, Debug ) ]
+//
+pub struct Layout
+{
+	prepared: Prepared          ,
+	width   : usize             ,
+	cuts    : Vec< SplitPoint > , // The split points we cut at.
+	lines   : Vec< Line >       ,
+}
+
+
+
+impl Layout
+{
+	pub fn prepared( &self ) -> &Prepared         { &self.prepared }
+	pub fn width   ( &self ) -> usize             {  self.width    }
+	pub fn cuts    ( &self ) -> &[ SplitPoint ]   { &self.cuts     }
+	pub fn lines   ( &self ) -> &[ Line ]         { &self.lines    }
+
+
+	// The line and column in the wrapped text of a byte offset in the unwrapped text. The column is in units of the ruler.
+	//
+	// - Offsets within a grapheme cluster map to where the cluster starts.
+	// - Offsets in the whitespace a cut consumed map to the end of the line before it, so before the glue. When a cut
+	//   consumes nothing, like a hyphenation cut, the offset is on the start of the next line.
+	// - Offsets in text replaced by the unbroken value of a split map to where the replacement starts.
+	// - Whitespace consumed by a cut at the start of the text maps to the start of the first line.
+	//
+	pub fn position( &self, offset: ByteOffset ) -> Result< ( usize, WidthOffset ), &'static str >
+	{
+		if offset.0 > self.prepared.text.len() { return Err( "Offset is not within the text" ) }
+
+		for ( i, line ) in self.lines.iter().enumerate()
+		{
+			// Before the start of the line means in what the cut before it consumed.
+			//
+			if offset < line.start
+			{
+				return Ok( if i == 0 { ( 0, WidthOffset( 0 ) ) } else { ( i - 1, self.lines[ i - 1 ].width() ) } );
+			}
+
+			// The end of the text belongs to the last line.
+			//
+			if offset < line.end  ||  i == self.lines.len() - 1
+			{
+				return Ok(( i, line.column( offset ) ));
+			}
+		}
+
+		unreachable!()
+	}
+
+
+	// The byte offset in the unwrapped text at a line and column of the wrapped text, the reverse of position.
+	// A column within the glue or beyond the end of the line gives the end of the line.
+	//
+	pub fn offset( &self, line: usize, column: WidthOffset ) -> Result< ByteOffset, &'static str >
+	{
+		match self.lines.get( line )
+		{
+			Some( line ) => Ok( line.offset( column ) ),
+			None         => Err( "Line is not within the layout" ),
+		}
+	}
+
+
+	// The wrapped text, the same as Wrapper::wrap gives.
+	//
+	pub fn text( &self ) -> String
+	{
+		let mut out = String::with_capacity( self.prepared.text.len() + self.lines.len() * 2 );
+
+		for line in &self.lines
+		{
+			out.push_str( &line.text );
+			out.push_str( &line.glue );
+		}
+
+		out
+	}
+}
+
+
+
+// One line of a Layout. Start and end are byte offsets in the unwrapped text, text is what the line shows of that, and glue
+// is what ends the line, which is empty for the last one.
+//
+#[ derive( PartialEq, Eq, Clone, Debug ) ]
+//
+pub struct Line
+{
+	pub start  : ByteOffset                        ,
+	pub end    : ByteOffset                        ,
+	pub text   : String                            ,
+	pub glue   : String                            ,
+	pub columns: Vec<( ByteOffset, WidthOffset )> , // The column of every grapheme boundary between start and end, sorted.
+}
+
+
+
+impl Line
+{
+	// The width of the text of the line, without the glue.
+	//
+	pub fn width( &self ) -> WidthOffset
+	{
+		self.columns.last().map( |c| c.1 ).unwrap_or( WidthOffset( 0 ) )
+	}
+
+
+	// The column of a byte offset between start and end. Offsets within a grapheme cluster or within text replaced by the
+	// unbroken value of a split give the column where it starts.
+	//
+	fn column( &self, offset: ByteOffset ) -> WidthOffset
+	{
+		self.columns.iter().take_while( |c| c.0 <= offset ).last().map( |c| c.1 ).unwrap_or( WidthOffset( 0 ) )
+	}
+
+
+	// The byte offset at a column of the line. Columns within a grapheme cluster give its start, columns past the end of
+	// the text, in the glue, give the end of the line. Where several bytes share a column, because a soft hyphen or a
+	// zero width space was left out, the first of them is given.
+	//
+	fn offset( &self, column: WidthOffset ) -> ByteOffset
+	{
+		if column > self.width() { return self.columns.last().map( |c| c.0 ).unwrap_or( self.start ) }
+
+		let at = self.columns.iter().take_while( |c| c.1 <= column ).last().map( |c| c.1 );
+
+		self.columns.iter().find( |c| Some( c.1 ) == at ).map( |c| c.0 ).unwrap_or( self.start )
+	}
+}
+
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	use generator::unicode_standard::Xi           ;
+	use generator::hyphenation     ::Hyphenator   ;
+	use filter::single_letter      ::SingleLetter ;
+	use filter::binding            ::Binding      ;
+	use generator::combinator      ::{ Restrict, Spans };
+	use hyphenation_crate          ::Language     ;
+	use ruler::unicode_width       ::UnicodeWidth ;
+
+
+	//--------------------
+	// basic xi splitting
+	//
+	fn xi( string: &str, width: usize, prio: usize ) -> Result< String, &'static str >
+	{
+		let gen     = Box::new( Xi{ priority: prio } );
+
+		let wrapper = try!( Wrapper::new( width, vec![ gen ], Vec::new(), UnicodeWidth, false ) );
+
+		wrapper.wrap( string )
+	}
+
+
+
+	#[test]
+	fn width()
+	{
+		let w = Wrapper::new( 5, Vec::new(), Vec::new(), UnicodeWidth, false ).unwrap();
+		assert_eq!( w.width(), 5 );
+	}
+
+
+
+	#[test]
+	fn basic()
+	{
+		assert_eq!( xi( "ha ha ah", 3, 1 ), Ok( "ha\nha\nah".to_string() ) );
+	}
+
+
+	#[test]
+	fn basic_zero_priority()
+	{
+		assert_eq!( xi( "ha ha ah", 3, 0 ), Ok( "ha\nha\nah".to_string() ) );
+	}
+
+
+	#[test]
+	fn basic_high_priority()
+	{
+		assert_eq!( xi( "ha ha ah", 3, 99999 ), Ok( "ha\nha\nah".to_string() ) );
+	}
+
+
+
+	#[test]
+	fn consecutive_spaces()
+	{
+		assert_eq!( xi( "ha ha       ah", 3, 1 ), Ok( "ha\nha\nah".to_string() ) );
+	}
+
+
+
+	#[test]
+	fn consecutive_spaces_and_tabs()
+	{
+		assert_eq!( xi( "ha ha   \t   ah", 3, 1 ), Ok( "ha\nha\nah".to_string() ) );
+	}
+
+
+
+	#[test]
+	fn nbsp()
+	{
+		assert_eq!( xi( "foo b\u{A0}r baz", 6, 1 ), Ok( "foo\nb\u{A0}r\nbaz".to_string() ) );
+	}
+
+
+	#[test]
+	fn dont_split_every_space()
+	{
+		assert_eq!( xi( "foo bar baz fiend", 9, 1 ), Ok( "foo bar\nbaz fiend".to_string() ) );
+	}
+
+
+	#[test]
+	fn width_zero()
+	{
+		assert_eq!( xi( "foo bar baz", 0, 1 ), Err( "Wrapper.width cannot be zero" ) );
+	}
+
+
+	#[test]
+	fn whitespace_should_not_be_squeezed()
+	{
+		assert_eq!( xi( "foo \t a bar", 7, 1 ), Ok( "foo \t a\nbar".to_string() ) );
+	}
+
+
+	#[test]
+	fn whitespace_should_not_be_trimmed_left_on_first_line()
+	{
+		assert_eq!( xi( " \tfoo \t  bar  ", 4, 1 ), Ok( " \tfoo\nbar\n  ".to_string() ) );
+	}
+
+
+	#[test]
+	fn leadingspaces_blocking_split()
+	{
+		assert_eq!( xi( " a b c", 1, 1 ), Ok( "a\nb\nc".to_string() ) );
+	}
+
+
+	#[test]
+	fn whitespace_should_be_trimmed_on_every_line_yet_no_empty_strings_should_exist_in_output()
+	{
+		assert_eq!( xi( "foo   ssss bars", 4, 1 ), Ok( "foo\nssss\nbars".to_string() ) );
+	}
+
+
+	#[test]
+	fn dont_break_before_punctuation()
+	{
+		assert_eq!( xi( "a ! b : c ? d", 2, 0 ), Err( "No valid split point found" ) );
+	}
+
+
+	#[test]
+	fn hyphens()
+	{
+		assert_eq!( xi( "co\u{ad}ca-co‧la", 3, 1 ), Ok( "co-\nca-\nco‧\nla".to_string() ) );
+		assert_eq!( xi( "co\u{ad}ca-co‧la", 9, 1 ), Ok( "coca-co‧\nla".to_string() ) );
+
+		// A soft hyphen we don't break at takes no room.
+		//
+		let wrapper = Wrapper::new( 4, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ).unwrap();
+
+		assert_eq!( wrapper.wrap     ( "co\u{ad}ca" ), Ok( "coca".to_string() ) );
+		assert_eq!( wrapper.max_width( "co\u{ad}ca" ), 4                      );
+	}
+
+
+	#[test]
+	fn zero_width()
+	{
+		assert_eq!( xi( "foo\u{200B}bar baz", 3, 1 ), Ok( "foo\nbar\nbaz".to_string() ) );
+		assert_eq!( xi( "foo\u{200B}bar baz", 7, 1 ), Ok( "foobar\nbaz".to_string() ) );
+		assert_eq!( xi( "foo\u{2060}bar baz", 6, 1 ), Ok( "foobar\nbaz".to_string() ) );
+	}
+
+
+	#[test]
+	fn newlines_should_not_be_dropped()
+	{
+		assert_eq!( xi( "co\n\n\nla", 3, 1 ), Ok( "co\n\n\nla".to_string() ) );
+	}
+
+
+	#[test]
+	fn leading_and_trailing_newlines_should_not_be_dropped()
+	{
+		assert_eq!( xi( "\ncola\n", 4, 1 ), Ok( "\ncola\n".to_string() ) );
+	}
+
+
+	#[test]
+	fn trailing_whitespace_is_not_a_line_break()
+	{
+		assert_eq!( xi( "hello "     , 80, 0 ), Ok( "hello "    .to_string() ) );
+		assert_eq!( xi( "hello\t"    , 80, 0 ), Ok( "hello\t"   .to_string() ) );
+		assert_eq!( xi( "foo bar \t ", 10, 0 ), Ok( "foo bar \t ".to_string() ) );
+		assert_eq!( xi( "foo\nbar  " , 80, 0 ), Ok( "foo\nbar  " .to_string() ) );
+
+		let wrapper = Builder::new( 4 ).break_word( true ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "hello " ), Ok( "hell\no ".to_string() ) );
+		assert_eq!( wrapper.layout( "hello " ).unwrap().lines().len(), 2 );
+		assert_eq!( wrapper.fit( "ab cd ", 2 ), Ok(( 3, "ab\ncd ".to_string() )) );
+	}
+
+	//-------------
+	// Hyphenation
+	//
+	fn hyphenate( string: &str, width: usize ) -> Result< String, &'static str >
+	{
+		let c   = hyphenation_crate::load( Language::English_US ).unwrap();
+		let gen = Box::new( Hyphenator{ priority: 1, corpus: std::sync::Arc::new( c ), glue: "-\n".to_string(), quality: Default::default() } );
+
+		let wrapper = try!( Wrapper::new( width, vec![ gen ], Vec::new(), UnicodeWidth, false ) );
+
+		wrapper.wrap( string )
+	}
+
+
+	#[test]
+	fn hyphenation()
+	{
+		assert_eq!( hyphenate( "hyphenation", 7 ), Ok( "hyphen-\nation"   .to_string() ) );
+		assert_eq!( hyphenate( "hyphenation", 6 ), Ok( "hy-\nphen-\nation".to_string() ) );
+		assert_eq!( hyphenate( "hyphenation", 5 ), Ok( "hy-\nphen-\nation".to_string() ) );
+	}
+
+
+	#[test]
+	fn too_short()
+	{
+		assert_eq!( hyphenate( "hyphenation", 4 ), Err( "No valid split point found" ) );
+	}
+
+
+	//----------------------
+	// Combining Generators
+	//
+	fn combine( string: &str, width: usize, hyph_prio: usize, xi_prio: usize ) -> Result< String, &'static str >
+	{
+		let c    = hyphenation_crate::load( Language::English_US ).unwrap();
+		let hyph = Box::new( Hyphenator{ priority: hyph_prio, corpus: std::sync::Arc::new( c ), glue: "-\n".to_string(), quality: Default::default() } );
+		let xi   = Box::new( Xi{ priority: xi_prio } );
+
+		let reverse = try!( Wrapper::new( width, vec![ xi.clone(), hyph.clone() ], Vec::new(), UnicodeWidth, false ) );
+		let wrapper = try!( Wrapper::new( width, vec![ hyph      , xi           ], Vec::new(), UnicodeWidth, false ) );
+
+		let normal   = wrapper.wrap( string );
+		let reversed = reverse.wrap( string );
+
+		assert_eq!( normal, reversed );
+
+		normal
+	}
+
+
+	#[test]
+	fn combine_generators_basic()
+	{
+		assert_eq!( combine( "hyphenation is key", 7, 0, 0 ), Ok( "hyphen-\nation\nis key".to_string() ) );
+	}
+
+	#[test]
+	fn combine_priority()
+	{
+		assert_eq!( combine( "the hyphenation is key", 7, 0, 0 ), Ok( "the hy-\nphen-\nation\nis key".to_string() ) );
+		assert_eq!( combine( "the hyphenation is key", 7, 0, 3 ), Ok( "the hy-\nphen-\nation\nis key".to_string() ) );
+		assert_eq!( combine( "the hyphenation is key", 7, 0, 4 ), Ok( "the\nhyphen-\nation\nis key".to_string() ) );
+	}
+
+	//--------
+	// Ladder
+	//
+	fn ladder( string: &str, width: usize, max: usize ) -> Result< String, &'static str >
+	{
+		let wrapper = try!( Builder::new( width ).hyphenation( Language::English_US ).ladder( Some(( "hyphenation".to_string(), max )) ).build() );
+
+		wrapper.wrap( string )
+	}
+
+
+	#[test]
+	fn ladder_limit()
+	{
+		assert_eq!( combine( "a a hyphenation hyphenation", 11, 0, 0 ), Ok( "a a hyphen-\nation hy-\nphenation".to_string() ) );
+		assert_eq!( ladder ( "a a hyphenation hyphenation", 11, 2    ), Ok( "a a hyphen-\nation hy-\nphenation".to_string() ) );
+		assert_eq!( ladder ( "a a hyphenation hyphenation", 11, 1    ), Ok( "a a hyphen-\nation\nhyphenation"   .to_string() ) );
+	}
+
+
+	#[test]
+	fn ladder_when_nothing_else_fits()
+	{
+		assert_eq!( ladder( "the hyphenation is key", 7, 1 ), Ok( "the hy-\nphen-\nation\nis key".to_string() ) );
+	}
+
+
+	// The ladder counts the splits of a generator, whatever their glue. Soft hyphens look like hyphenation, but are Xi's.
+	//
+	#[test]
+	fn ladder_by_generator()
+	{
+		let c    = hyphenation_crate::load( Language::English_US ).unwrap();
+		let hyph = Box::new( Hyphenator{ priority: 0, corpus: std::sync::Arc::new( c ), glue: "=\n".to_string(), quality: Default::default() } );
+		let xi   = Box::new( Xi{ priority: 0 } );
+
+		let mut wrapper = Wrapper::new( 11, vec![ hyph, xi ], Vec::new(), UnicodeWidth, false ).unwrap();
+
+		wrapper.set_ladder( Some(( "hyphenation".to_string(), 1 )) );
+
+		assert_eq!( wrapper.wrap( "a a hyphenation hyphenation" ), Ok( "a a hyphen=\nation\nhyphenation".to_string() ) );
+
+
+		let wrapper = Builder::new( 6 ).hyphenation( Language::English_US ).ladder( Some(( "hyphenation".to_string(), 1 )) ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "aaaaa\u{AD}bb cc\u{AD}ddd" ), Ok( "aaaaa-\nbb cc-\nddd".to_string() ) );
+	}
+
+
+	//-----------
+	// Last line
+	//
+	fn last_line( string: &str, width: usize, last_line: LastLine ) -> Result< String, &'static str >
+	{
+		let mut wrapper = try!( Wrapper::new( width, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ) );
+
+		wrapper.set_last_line( Some( last_line ) );
+
+		wrapper.wrap( string )
+	}
+
+
+	#[test]
+	fn runt()
+	{
+		assert_eq!( xi       ( "foo bar baz qux a", 15, 0                    ), Ok( "foo bar baz qux\na".to_string() ) );
+		assert_eq!( last_line( "foo bar baz qux a", 15, LastLine::Words( 2 ) ), Ok( "foo bar baz\nqux a".to_string() ) );
+		assert_eq!( last_line( "foo bar baz qux a", 15, LastLine::Width( 5 ) ), Ok( "foo bar baz\nqux a".to_string() ) );
+		assert_eq!( last_line( "foo bar baz qux a", 15, LastLine::Width( 6 ) ), Ok( "foo bar\nbaz qux a".to_string() ) );
+	}
+
+
+	#[test]
+	fn runt_every_paragraph()
+	{
+		assert_eq!
+		(
+			  last_line( "foo bar baz qux a\nfoo bar baz qux a", 15, LastLine::Words( 2 ) )
+
+			, Ok( "foo bar baz\nqux a\nfoo bar baz\nqux a".to_string() )
+		);
+	}
+
+
+	#[test]
+	fn runt_that_cannot_be_helped()
+	{
+		assert_eq!( last_line( "aaaa bbbb c", 5, LastLine::Words( 3 ) ), Ok( "aaaa\nbbbb\nc".to_string() ) );
+	}
+
+
+	//----------
+	// Balanced
+	//
+	fn balanced( string: &str, width: usize ) -> Result< String, &'static str >
+	{
+		let mut wrapper = try!( Wrapper::new( width, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ) );
+
+		wrapper.set_balanced( true );
+
+		wrapper.wrap( string )
+	}
+
+
+	#[test]
+	fn balance()
+	{
+		assert_eq!( balanced( "foo bar baz qux a"          , 15 ), Ok( "foo bar\nbaz qux a"      .to_string() ) );
+		assert_eq!( balanced( "foo bar baz qux a foo bar"  , 15 ), Ok( "foo bar baz\nqux a foo bar".to_string() ) );
+		assert_eq!( balanced( "foo bar"                    , 15 ), Ok( "foo bar"                 .to_string() ) );
+	}
+
+
+	// Words that fit are not cut to make the lines even.
+	//
+	#[test]
+	fn balance_break_word()
+	{
+		let wrapper = Builder::new( 12 ).break_word( true ).balanced( true ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "aaaaaaaaaa bb"         ), Ok( "aaaaaaaaaa\nbb"        .to_string() ) );
+		assert_eq!( wrapper.wrap( "foo bar baz qux a"     ), Ok( "foo bar\nbaz qux a"    .to_string() ) );
+		assert_eq!( wrapper.wrap( "aaaaaaaaaaaaaaaa bb"   ), Ok( "aaaaaaaaaa\naaaaaa bb" .to_string() ) );
+	}
+
+
+	//-----------------
+	// Min & Max width
+	//
+	#[test]
+	fn min_width()
+	{
+		let wrapper = Builder::new( 5 ).hyphenation( Language::English_US ).build().unwrap();
+		let words   = Builder::new( 5 )                                    .build().unwrap();
+		let breaks  = Builder::new( 5 ).break_word( true )                 .build().unwrap();
+
+		assert_eq!( wrapper.min_width( "hyphenation is key" ), 5  );
+		assert_eq!( words  .min_width( "hyphenation is key" ), 11 );
+		assert_eq!( words  .min_width( "foo \t bar"         ), 3  );
+		assert_eq!( breaks .min_width( "hyphenation is key" ), 1  );
+
+		assert!( wrapper.wrap( "hyphenation is key" ).is_ok() );
+	}
+
+
+	#[test]
+	fn max_width()
+	{
+		let words = Wrapper::new( 5, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ).unwrap();
+
+		assert_eq!( words.max_width( "foo bar\nbazinga" ), 7  );
+		assert_eq!( words.max_width( "foo bar baz"      ), 11 );
+		assert_eq!( words.max_width( ""                 ), 0  );
+	}
+
+
+	//-------------
+	// Fit to lines
+	//
+	#[test]
+	fn fit()
+	{
+		let words = Wrapper::new( 5, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ).unwrap();
+
+		assert_eq!( words.fit( "foo bar baz qux a", 1 ), Ok(( 17, "foo bar baz qux a"  .to_string() )) );
+		assert_eq!( words.fit( "foo bar baz qux a", 2 ), Ok((  9, "foo bar\nbaz qux a" .to_string() )) );
+		assert_eq!( words.fit( "foo bar baz qux a", 9 ), Ok((  3, "foo\nbar\nbaz\nqux\na".to_string() )) );
+	}
+
+
+	// With the ladder, nothing fits at width 6, so a binary search alone goes on to 7 and misses that 5 gives five lines.
+	//
+	#[test]
+	fn fit_ladder()
+	{
+		let hyphens = |_: &str| [ 2, 5, 10 ].iter().map( |&at|
+		{
+			let mut split = SplitPoint::new( at, at, 0 );
+
+			split.glue      = "-\n"    .to_string();
+			split.generator = "hyphens".to_string();
+			split
+
+		}).collect::< Vec< SplitPoint > >();
+
+		let wrapper = Builder::new( 5 )
+
+			.generator( hyphens                            )
+			.generator( Xi{ priority: 0 }                  )
+			.ladder   ( Some(( "hyphens".to_string(), 1 )) )
+			.build    ()
+			.unwrap   ()
+		;
+
+		assert_eq!( wrapper.fit( "aaa aaa aaaaaaa aaa", 5 ), Ok(( 5, "aaa\naaa\naa-\naaaaa\naaa".to_string() )) );
+		assert_eq!( wrapper.fit( "aaa aaa aaaaaaa aaa", 4 ), Ok(( 7, "aaa aaa\naaaaaaa\naaa"   .to_string() )) );
+	}
+
+
+	#[test]
+	fn fit_impossible()
+	{
+		let words = Wrapper::new( 5, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ).unwrap();
+
+		assert_eq!( words.fit( "foo\nbar", 1 ), Err( "Mandatory breaks give more lines than requested" ) );
+		assert_eq!( words.fit( "foo"      , 0 ), Err( "Cannot fit text in zero lines"                    ) );
+	}
+
+
+	//----------
+	// Prepared
+	//
+	#[test]
+	fn prepared_gives_same_result_as_wrap()
+	{
+		let text = "the hyphenation is key\nto « garbage collection »";
+
+		let mut wrapper  = Builder::new( 5 ).hyphenation( Language::English_US ).break_word( true ).build().unwrap();
+		let     prepared = wrapper.prepare( text );
+
+		assert_eq!( prepared.text(), text );
+
+		for width in 1..30
+		{
+			wrapper.set_width( width ).unwrap();
+
+			assert_eq!( wrapper.wrap_prepared( &prepared, width ), wrapper.wrap( text ) );
+		}
+
+		assert_eq!( wrapper.wrap_prepared( &prepared, 0 ), Err( "Wrapper.width cannot be zero" ) );
+	}
 
 
-		let mut out   = String::with_capacity( line.len() + cuts.len() * 2 );
-		let mut start = 0;
 
-		let mut c = 0;
-		let mut e = 0;
+	// Apply the edit to a layout of text and check it's the same as a layout of the edited text.
+	//
+	fn edit( text: &str, range: Range< usize >, insert: &str ) -> Range< usize >
+	{
+		let wrapper = Builder::new( 10 ).hyphenation( Language::English_US ).generator( Xi{ priority: 1 } ).break_word( true ).build().unwrap();
 
+		edit_with( &wrapper, text, range, insert )
+	}
 
-		loop
-		{
-			let cut;
 
-			if let Some( c_candidate ) = cuts.get( c )
-			{
-				if let Some( e_candidate ) = extra_splits.get( e ) {
+	// Edits the layout of text and checks it against a fresh layout of the edited text.
+	//
+	fn edit_with( wrapper: &Wrapper< UnicodeWidth >, text: &str, range: Range< usize >, insert: &str ) -> Range< usize >
+	{
+		let mut layout = wrapper.layout( text ).unwrap();
+		let old        = layout.lines.clone();
 
-					cut = if c_candidate.start < e_candidate.start { c += 1; c_candidate } else { e += 1; e_candidate }; }
+		let changed = wrapper.edit( &mut layout, range.clone(), insert ).unwrap();
 
+		let edited = format!( "{}{}{}", &text[ ..range.start ], insert, &text[ range.end.. ] );
+		let fresh  = wrapper.layout( &edited ).unwrap();
 
-				else
-				{
-					c += 1;
-					cut = *c_candidate;
-				}
-			}
+		assert_eq!( layout.text()            , wrapper.wrap( &edited ).unwrap() );
+		assert_eq!( layout.lines()           , fresh.lines()                    );
+		assert_eq!( layout.prepared.splits   , fresh.prepared.splits            );
+		assert_eq!( layout.prepared.b2w      , fresh.prepared.b2w               );
+		assert_eq!( layout.prepared.width    , fresh.prepared.width             );
 
+		// The lines outside of changed are the same as before.
+		//
+		let after = fresh.lines.len() - changed.end;
 
-			else if let Some( e_candidate ) = extra_splits.get( e )
-			{
-				e += 1;
-				cut = e_candidate;
-			}
+		assert!( changed.start <= changed.end                                                                          );
+		assert!( old.len() >= changed.start + after                                                                    );
+		assert_eq!( &old[ ..changed.start ], &fresh.lines[ ..changed.start ]                                          );
+		assert!( old[ old.len() - after.. ].iter().zip( &fresh.lines[ changed.end.. ] ).all( |( a, b )| a.text == b.text && a.glue == b.glue ) );
 
+		changed
+	}
 
-			else { break }
 
+	#[test]
+	fn edit_rewraps()
+	{
+		let text = "one two\nthe hyphenation\n\nof garbage collection";
 
-			// We should never try to cut at the end of the string, but it happens.
-			// After some time, this can be commented out.
-			//
-			debug_assert!( cut.start.0 != line.len() );
+		assert_eq!( edit( text, 0..3   , "three" ), 0..1 );
+		assert_eq!( edit( text, 12..12 , "x"     ), 1..4 );
+		assert_eq!( edit( text, 25..25 , "\n"    ), 4..5 );
+		assert_eq!( edit( text, 46..46 , " calendula" ), 5..7 );
+		assert_eq!( edit( ""  , 0..0   , "calendula"  ), 0..1 );
 
-			out.push_str( &line[ start..cut.start.0 ] );
+		// Joining the first two paragraphs wraps the same, so no lines change.
+		//
+		assert_eq!( edit( text, 7..8   , " "     ), 6..6 );
 
+		// The line break before the edit ends up at the end of the text.
+		//
+		assert_eq!( edit( "hyphenation\nhyphenation hyphenation\n", 12..36, "" ), 1..2 );
+	}
 
-			if cut.start.0 != 0  &&  cut.end.0 != line.len() {
 
-				out.push_str( &cut.glue ); }
+	#[test]
+	fn edit_everywhere()
+	{
+		let wrapper = Builder::new( 10 ).hyphenation( Language::English_US ).generator( Xi{ priority: 1 } ).break_word( true ).build().unwrap();
 
+		let texts   = [ "one two\nthe hyphenation\n\nof garbage", "hyphenation\nhyphenation hyphenation\n", "\n\nfoo bar\n", "" ];
+		let inserts = [ "", "x", " ", "\n", "hyphenation ", "a\nb" ];
 
-			start = cut.end.0;
+		for text in texts.iter()
+		{
+			for start in 0..text.len() + 1 { for end in start..text.len() + 1 { for insert in inserts.iter()
+			{
+				edit_with( &wrapper, text, start..end, insert );
+			}}}
 		}
+	}
 
 
-		out.push_str( &line[ start..line.len() ] );
+	// Random edits of random texts give the same layout as wrapping the edited text.
+	//
+	#[test]
+	fn edit_random()
+	{
+		let pieces = [ " ", "  ", "\t", "\n", "\r", "\r\n", "\n\n", "a", "bb", "5", " km", "v ", "Dr.", "hyphenation", "世界", "e\u{301}", "\u{AD}", "\u{200B}", "\u{2060}", "\u{A0}", "«", "»", "!", " : ", "—", "-" ];
 
-		Ok( out )
+		// A xorshift generator, so the test is the same on every run.
+		//
+		let mut state = 0x9E37_79B9_7F4A_7C15_u64;
 
-	}
-}
+		let mut random = |n: usize|
+		{
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
 
+			( state % n as u64 ) as usize
+		};
 
+		for _ in 0..1000
+		{
+			let width    = 1 + random( 12 );
+			let wrappers = vec!
+			[
+				Builder::terminal( width ).break_word( true ).build().unwrap(),
+				Builder::english ( width ).break_word( true ).balanced( true ).build().unwrap(),
+				Builder::french  ( width ).break_word( true ).last_line( Some( LastLine::Width( 3 ) ) ).build().unwrap(),
+				Builder::new     ( width ).filter( SingleLetter::czech() ).filter( Binding::english() ).break_word( true ).build().unwrap(),
+			];
+
+			let text : String = ( 0..random( 10 ) ).map( |_| pieces[ random( pieces.len() ) ] ).collect();
+			let chars: Vec< usize > = ( 0..text.len() + 1 ).filter( |&i| text.is_char_boundary( i ) ).collect();
+
+			let ( a, b ) = ( chars[ random( chars.len() ) ], chars[ random( chars.len() ) ] );
+			let insert: String = ( 0..random( 3 ) ).map( |_| pieces[ random( pieces.len() ) ] ).collect();
+
+			for wrapper in &wrappers
+			{
+				edit_with( wrapper, &text, std::cmp::min( a, b )..std::cmp::max( a, b ), &insert );
+			}
+		}
+	}
 
 
-#[cfg(test)]
-mod tests
-{
-	use super::*;
+	// Restrict, Spans and the Pattern filter are about the whole text, so an edit in one paragraph can change the splits of
+	// others.
+	//
+	#[test]
+	fn edit_not_local()
+	{
+		let restrict = Restrict{ generator: Xi{ priority: 0 }, range: 0..10 };
+		let wrapper  = Wrapper::new( 4, vec![ Box::new( restrict ) ], vec![], UnicodeWidth, true ).unwrap();
 
-	use generator::unicode_standard::Xi           ;
-	use generator::hyphenation     ::Hyphenator   ;
-	use hyphenation_crate          ::Language     ;
-	use ruler::unicode_width       ::UnicodeWidth ;
+		edit_with( &wrapper, "aa bb\ncc dd ee", 0..0, "x "  );
+		edit_with( &wrapper, "aa bb\ncc dd ee", 7..9, ""    );
 
 
-	//--------------------
-	// basic xi splitting
-	//
-	fn xi( string: &str, width: usize, prio: usize ) -> Result< String, &'static str >
-	{
-		let gen     = Box::new( Xi{ priority: prio } );
+		let quoted = |text: &str| text.match_indices( '"' ).collect::< Vec<_> >().chunks( 2 )
 
-		let wrapper = try!( Wrapper::new( width, vec![ gen ], Vec::new(), UnicodeWidth, false ) );
+			.filter( |pair| pair.len() == 2 )
+			.map( |pair| pair[ 0 ].0..pair[ 1 ].0 )
+			.collect()
+		;
 
-		wrapper.wrap( string )
-	}
+		let spans   = Spans{ generator: Xi{ priority: 0 }, spans: quoted };
+		let wrapper = Wrapper::new( 4, vec![ Box::new( spans ) ], vec![], UnicodeWidth, true ).unwrap();
 
+		edit_with( &wrapper, "a \"b c\n\nd e\" f g", 2..3, ""   );
+		edit_with( &wrapper, "a b c\n\nd e f g"      , 2..2, "\"" );
 
 
-	#[test]
-	fn width()
-	{
-		let w = Wrapper::new( 5, Vec::new(), Vec::new(), UnicodeWidth, false ).unwrap();
-		assert_eq!( w.width(), 5 );
-	}
+		// The match spans the line break, so removing its end enables the split in the first paragraph again.
+		//
+		let pattern = filter::pattern::Pattern::new( r"\[\[(?s:.)*?\]\]" ).unwrap();
+		let wrapper = Wrapper::new( 4, vec![ Box::new( Xi{ priority: 0 } ) ], vec![ Box::new( pattern ) ], UnicodeWidth, true ).unwrap();
 
+		edit_with( &wrapper, "[[aa bb\ncc]] dd\nee", 10..12, ""   );
+		edit_with( &wrapper, "[[aa bb\ncc dd\nee"  , 10..10, "]]" );
+	}
 
 
 	#[test]
-	fn basic()
+	fn edit_out_of_range()
 	{
-		assert_eq!( xi( "ha ha ah", 3, 1 ), Ok( "ha\nha\nah".to_string() ) );
+		let wrapper    = Wrapper::new( 10, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ).unwrap();
+		let mut layout = wrapper.layout( "né" ).unwrap();
+
+		assert_eq!( wrapper.edit( &mut layout, 2..3, "" ), Err( "Edit range is not within the text" ) );
+		assert_eq!( wrapper.edit( &mut layout, 0..4, "" ), Err( "Edit range is not within the text" ) );
+		assert_eq!( layout.text(), "né" );
 	}
 
 
-	#[test]
-	fn basic_zero_priority()
+	fn refill( width: usize ) -> Wrapper< UnicodeWidth >
 	{
-		assert_eq!( xi( "ha ha ah", 3, 0 ), Ok( "ha\nha\nah".to_string() ) );
+		Builder::new( width ).hyphenation( Language::English_US ).generator( Xi{ priority: 1 } ).build().unwrap()
 	}
 
 
 	#[test]
-	fn basic_high_priority()
+	fn unwrap()
 	{
-		assert_eq!( xi( "ha ha ah", 3, 99999 ), Ok( "ha\nha\nah".to_string() ) );
-	}
+		let text    = "the hyphenation of well-known garbage\n\nis a collection";
+		let wrapped = refill( 7 ).wrap( text ).unwrap();
 
+		assert_eq!( wrapped, "the hy-\nphen-\nation\nof\nwell-\nknown\ngarbage\n\nis a\ncollec-\ntion" );
 
+		assert_eq!( refill( 7  ).unwrap( &wrapped ), text );
+		assert_eq!( refill( 20 ).refill( &wrapped ), refill( 20 ).wrap( text ) );
 
-	#[test]
-	fn consecutive_spaces()
-	{
-		assert_eq!( xi( "ha ha       ah", 3, 1 ), Ok( "ha\nha\nah".to_string() ) );
-	}
+		// A hyphen the dictionary doesn't know of stays.
+		//
+		assert_eq!( refill( 7 ).unwrap( "garb-\nage" ), "garb-age" );
 
+		// Lines may end in "\r\n".
+		//
+		assert_eq!( refill( 7 ).unwrap( "ab\r\ncd"                ), "ab cd"            );
+		assert_eq!( refill( 7 ).unwrap( "hyphen-\r\nation\r\n\r\nab\r" ), "hyphenation\r\n\r\nab\r" );
+	}
 
 
 	#[test]
-	fn consecutive_spaces_and_tabs()
+	fn unwrap_keeps_line_breaks_nobody_claims()
 	{
-		assert_eq!( xi( "ha ha   \t   ah", 3, 1 ), Ok( "ha\nha\nah".to_string() ) );
-	}
+		let xi      = Box::new( Xi{ priority: 0 } );
+		let wrapper = Wrapper::new( 5, vec![ xi ], Vec::new(), UnicodeWidth, false ).unwrap();
 
+		assert_eq!( wrapper.unwrap( "a\nb\n \nc\n" ), "a b\n \nc\n" );
 
+		let wrapper = Wrapper::new( 5, Vec::new(), Vec::new(), UnicodeWidth, false ).unwrap();
 
-	#[test]
-	fn nbsp()
-	{
-		assert_eq!( xi( "foo b\u{A0}r baz", 6, 1 ), Ok( "foo\nb\u{A0}r\nbaz".to_string() ) );
+		assert_eq!( wrapper.unwrap( "a\nb" ), "a\nb" );
 	}
 
 
 	#[test]
-	fn dont_split_every_space()
+	fn annotate()
 	{
-		assert_eq!( xi( "foo bar baz fiend", 9, 1 ), Ok( "foo bar\nbaz fiend".to_string() ) );
-	}
+		assert_eq!( refill( 7 ).annotate( "the hyphenation of 世界", SOFT_HYPHEN, WBR ), "the hy\u{AD}phen\u{AD}ation of 世<wbr>界" );
+		assert_eq!( refill( 7 ).annotate( "garbage-collection"    , SOFT_HYPHEN, WBR ), "gar\u{AD}bage-<wbr>col\u{AD}lec\u{AD}tion" );
+		assert_eq!( refill( 7 ).annotate( "co\u{AD}ca a\u{200B}b"  , "=", "|" ), "co=ca a|b" );
 
+		let xi      = Box::new( Xi{ priority: 0 } );
+		let french  = Box::new( filter::french::French{ narrow_nbsp: true } );
+		let wrapper = Wrapper::new( 5, vec![ xi ], vec![ french ], UnicodeWidth, false ).unwrap();
 
-	#[test]
-	fn width_zero()
-	{
-		assert_eq!( xi( "foo bar baz", 0, 1 ), Err( "Wrapper.width cannot be zero" ) );
-	}
+		assert_eq!( wrapper.annotate( "oui !\nnon", SOFT_HYPHEN, ZERO_WIDTH_SPACE ), "oui\u{202F}!\nnon" );
 
 
-	#[test]
-	fn whitespace_should_not_be_squeezed()
-	{
-		assert_eq!( xi( "foo \t a bar", 7, 1 ), Ok( "foo \t a\nbar".to_string() ) );
+		let xi      = Box::new( Xi{ priority: 0 } );
+		let binding = Box::new( Binding::english() );
+		let wrapper = Wrapper::new( 5, vec![ xi ], vec![ binding ], UnicodeWidth, false ).unwrap();
+
+		assert_eq!( wrapper.annotate( "Dr. Smith walks 10  km", SOFT_HYPHEN, WBR ), "Dr.\u{A0}Smith walks 10\u{A0}\u{A0}km" );
 	}
 
 
-	#[test]
-	fn whitespace_should_not_be_trimmed_left_on_first_line()
+	// Keeps a short description of every event.
+	//
+	struct Record( std::sync::Arc< std::sync::Mutex< Vec< String > > > );
+
+	impl Observe for Record
 	{
-		assert_eq!( xi( " \tfoo \t  bar  ", 4, 1 ), Ok( " \tfoo\nbar\n  ".to_string() ) );
+		fn event( &self, event: &Event )
+		{
+			let line = match *event
+			{
+				Event::Grapheme  { .. }                  => return,
+				Event::Generated { generator, split }    => format!( "generated {} {}-{}", generator, split.start.0, split.end.0 ),
+				Event::Filtered  { filter, after, .. }   => format!( "filtered {} {}-{} {}", filter, after.start.0, after.end.0, after.enabled ),
+				Event::Line      { start, end }          => format!( "line {}-{}", start.0, end.0 ),
+				Event::Considered{ split, score }        => format!( "considered {}-{} {}", split.start.0, split.end.0, score   ),
+				Event::Chosen    { split }               => format!( "chosen {}-{}", split.start.0, split.end.0 ),
+				Event::BrokenWord{ split }               => format!( "broken {}", split.start.0 ),
+			};
+
+			self.0.lock().unwrap().push( line );
+		}
 	}
 
 
 	#[test]
-	fn leadingspaces_blocking_split()
+	fn observer()
 	{
-		assert_eq!( xi( " a b c", 1, 1 ), Ok( "a\nb\nc".to_string() ) );
-	}
+		let xi      = Box::new( Xi{ priority: 0 } );
+		let french  = Box::new( filter::french::French::default() );
+		let events  = std::sync::Arc::new( std::sync::Mutex::new( Vec::new() ) );
 
+		let mut wrapper = Wrapper::new( 4, vec![ xi ], vec![ french ], UnicodeWidth, true ).unwrap();
 
-	#[test]
-	fn whitespace_should_be_trimmed_on_every_line_yet_no_empty_strings_should_exist_in_output()
-	{
-		assert_eq!( xi( "foo   ssss bars", 4, 1 ), Ok( "foo\nssss\nbars".to_string() ) );
+		wrapper.set_observer( Some( Box::new( Record( events.clone() ) ) ) );
+
+		assert_eq!( wrapper.wrap( "ab c %" ), Ok( "ab\nc %".to_string() ) );
+
+		assert_eq!
+		(
+			  *events.lock().unwrap()
+
+			, vec!
+			  [
+				  "generated 0 2-3"      ,
+				  "generated 0 4-5"      ,
+				  "generated 0 6-6"      ,
+				  "filtered 0 4-5 false" ,
+				  "line 0-4"             ,
+				  "considered 2-3 2"     ,
+				  "considered 4-5 4"     ,
+				  "considered 6-6 6"     ,
+				  "chosen 2-3"           ,
+			  ]
+		);
 	}
 
 
 	#[test]
-	fn dont_break_before_punctuation()
+	fn closures()
 	{
-		assert_eq!( xi( "a ! b : c ? d", 2, 0 ), Err( "No valid split point found" ) );
+		// Break after arrows, but never after "a".
+		//
+		let arrows = |text: &str| text.match_indices( "->" ).map( |( i, _ )| SplitPoint::new( i + 2, i + 2, 0 ) ).collect();
+
+		let no_a = |text: &str, splits: &mut Vec< SplitPoint >|
+		{
+			for split in splits.iter_mut().filter( |s| text[ ..s.start.0 ].ends_with( "a->" ) ) { split.enabled = false }
+		};
+
+		let wrapper = Wrapper::new( 6, vec![ Box::new( arrows ) ], vec![ Box::new( no_a ) ], UnicodeWidth, false ).unwrap();
+
+		assert_eq!( wrapper.wrap( "x->y->a->b" ), Ok( "x->y->\na->b".to_string() ) );
 	}
 
 
 	#[test]
-	fn hyphens()
+	fn provenance()
 	{
-		assert_eq!( xi( "co\u{ad}ca-co‧la", 3, 1 ), Ok( "co\u{ad}\nca-\nco‧\nla".to_string() ) );
+		let wrapper = Builder::new( 10 )
+
+			.hyphenation( Language::English_US                       )
+			.generator  ( Xi{ priority: 1 }                          )
+			.filter     ( filter::french::French{ narrow_nbsp: true } )
+			.build      ()
+			.unwrap     ()
+		;
+
+		let prepared = wrapper.prepare( "hyphenation « a »" );
+
+		let found = |start: usize| prepared.splits().iter().find( |s| s.start.0 == start ).unwrap().clone();
+
+		assert_eq!( found(  2 ).generator, "hyphenation" );
+		assert_eq!( found( 11 ).generator, "xi"          );
+		assert!   ( found( 11 ).filters  .is_empty()     );
+		assert_eq!( found( 14 ).filters  , vec![ "french" ] );
 	}
 
 
+	// A filter that removes splits doesn't make the others look changed.
+	//
 	#[test]
-	fn newlines_should_not_be_dropped()
+	fn provenance_retain()
 	{
-		assert_eq!( xi( "co\n\n\nla", 3, 1 ), Ok( "co\n\n\nla".to_string() ) );
+		let retain = |_: &str, splits: &mut Vec<SplitPoint>| splits.retain( |s| s.start.0 != 2 );
+		let french = Box::new( filter::french::French{ narrow_nbsp: true } );
+
+		let wrapper  = Wrapper::new( 10, vec![ Box::new( Xi{ priority: 0 } ) ], vec![ Box::new( retain ), french ], UnicodeWidth, false ).unwrap();
+		let prepared = wrapper.prepare( "ab cd « a »" );
+
+		let found = |start: usize| prepared.splits().iter().find( |s| s.start.0 == start ).cloned();
+
+		assert!   ( found( 2 ).is_none()                        );
+		assert!   ( found( 5 ).unwrap().filters.is_empty()      );
+		assert_eq!( found( 8 ).unwrap().filters, vec![ "french" ] );
 	}
 
 
 	#[test]
-	fn leading_and_trailing_newlines_should_not_be_dropped()
+	fn explain_avoided()
 	{
-		assert_eq!( xi( "\ncola\n", 4, 1 ), Ok( "\ncola\n".to_string() ) );
+		let reasons = |wrapper: Wrapper< UnicodeWidth >, text: &str| -> Vec< Vec< Reason > >
+		{
+			wrapper.explain( text ).unwrap().lines.iter().map( |l| l.rejected.iter().map( |r| r.1.clone() ).collect() ).collect()
+		};
+
+		let ladder = Builder::new( 11 ).hyphenation( Language::English_US ).ladder( Some(( "hyphenation".to_string(), 1 )) ).build().unwrap();
+
+		assert!( reasons( ladder, "a a hyphenation hyphenation" )[ 1 ].contains( &Reason::Ladder ) );
+
+
+		let last_line = Builder::new( 15 ).last_line( Some( LastLine::Words( 2 ) ) ).build().unwrap();
+
+		assert_eq!( reasons( last_line, "foo bar baz qux a" )[ 0 ].last(), Some( &Reason::LastLine ) );
+
+
+		// Splits are looked at in the order of start plus priority, so the split in "aa" comes after the one before "dd" that
+		// doesn't fit, and is never reached.
+		//
+		let early   = |_: &str| vec![ SplitPoint::new( 1, 1, 20 ) ];
+		let reached = Builder::new( 6 ).generator( early ).generator( Xi{ priority: 0 } ).build().unwrap();
+
+		assert_eq!
+		(
+			  reasons( reached, "aa bb cc dd" )[ 0 ]
+
+			, vec![ Reason::NotReached, Reason::Outscored{ score: 2, winner: 5 }, Reason::Overflow( WidthOffset( 2 ) ) ]
+		);
 	}
 
-	//-------------
-	// Hyphenation
+
+	// The reasons are about the width balancing chose, not the width of the wrapper.
 	//
-	fn hyphenate( string: &str, width: usize ) -> Result< String, &'static str >
+	#[test]
+	fn explain_balanced()
 	{
-		let c   = hyphenation_crate::load( Language::English_US ).unwrap();
-		let gen = Box::new( Hyphenator{ priority: 1, corpus: c, glue: "-\n".to_string() } );
+		let wrapper     = Builder::new( 12 ).balanced( true ).build().unwrap();
+		let explanation = wrapper.explain( "aaa bbb ccc d" ).unwrap();
 
-		let wrapper = try!( Wrapper::new( width, vec![ gen ], Vec::new(), UnicodeWidth, false ) );
+		assert_eq!( explanation.width, 7 );
 
-		wrapper.wrap( string )
+		assert_eq!
+		(
+			  explanation.lines[ 0 ].rejected.iter().map( |r| r.1.clone() ).collect::< Vec<_> >()
+
+			, vec![ Reason::Outscored{ score: 3, winner: 7 }, Reason::Balanced( WidthOffset( 4 ) ) ]
+		);
+
+		assert_eq!( explanation.to_string().lines().nth( 2 ), Some( "    11-12: over the balanced width by 4" ) );
 	}
 
 
 	#[test]
-	fn hyphenation()
+	fn explain()
 	{
-		assert_eq!( hyphenate( "hyphenation", 7 ), Ok( "hyphen-\nation"   .to_string() ) );
-		assert_eq!( hyphenate( "hyphenation", 6 ), Ok( "hy-\nphen-\nation".to_string() ) );
-		assert_eq!( hyphenate( "hyphenation", 5 ), Ok( "hy-\nphen-\nation".to_string() ) );
+		let wrapper = Builder::new( 10 )
+
+			.hyphenation( Language::English_US              )
+			.generator  ( Xi{ priority: 1 }                 )
+			.filter     ( filter::french::French::default() )
+			.build      ()
+			.unwrap     ()
+		;
+
+		let explanation = wrapper.explain( "the hyphenation « is »\nkey" ).unwrap();
+
+		let reasons: Vec< Vec< Reason > > = explanation.lines.iter()
+
+			.map( |l| l.rejected.iter().map( |r| r.1.clone() ).collect() )
+			.collect()
+		;
+
+		assert_eq!
+		(
+			  reasons
+
+			, vec!
+			  [
+				  vec![ Reason::Outscored{ score:  4, winner:  6 }, Reason::Overflow( WidthOffset( 1 ) ) ],
+				  vec![ Reason::Outscored{ score: 10, winner: 16 }, Reason::Overflow( WidthOffset( 1 ) ) ],
+				  vec![ Reason::Disabled( Some( "french".to_string() ) ), Reason::Disabled( Some( "french".to_string() ) ) ],
+				  vec![],
+			  ]
+		);
+
+		assert_eq!
+		(
+			  explanation.to_string().lines().collect::< Vec<_> >()
+
+			, vec!
+			  [
+				  "  1 \"the hy\" cut at 6-6"            ,
+				  "    3-4: score 4 is lower than 6"      ,
+				  "    10-10: over the width by 1"        ,
+				  "  2 \"phenation\" cut at 15-16"       ,
+				  "    10-10: score 10 is lower than 16"  ,
+				  "    18-19: over the width by 1"        ,
+				  "  3 \"« is »\" cut at 24-25"          ,
+				  "    18-19: disabled by french"         ,
+				  "    21-22: disabled by french"         ,
+				  "  4 \"key\""                          ,
+			  ]
+		);
 	}
 
 
 	#[test]
-	fn too_short()
+	fn lines_end_at_mandatory_breaks()
 	{
-		assert_eq!( hyphenate( "hyphenation", 4 ), Err( "No valid split point found" ) );
+		let wrapper = Wrapper::new( 10, vec![ Box::new( Xi{ priority: 0 } ) ], Vec::new(), UnicodeWidth, false ).unwrap();
+		let layout  = wrapper.layout( "\na b\nc\n" ).unwrap();
+
+		let lines: Vec< ( &str, &str ) > = layout.lines().iter().map( |l| ( l.text.as_str(), l.glue.as_str() ) ).collect();
+
+		// Xi doesn't give a split for a newline at the very end.
+		//
+		assert_eq!( lines          , vec![ ( "", "\n" ), ( "a b", "\n" ), ( "c\n", "" ) ] );
+		assert_eq!( layout.text()  , "\na b\nc\n"                                        );
+		assert_eq!( layout.position( ByteOffset( 4 ) ), Ok(( 1, WidthOffset( 3 ) ))    );
+		assert_eq!( layout.position( ByteOffset( 5 ) ), Ok(( 2, WidthOffset( 0 ) ))    );
 	}
 
 
-	//----------------------
-	// Combining Generators
-	//
-	fn combine( string: &str, width: usize, hyph_prio: usize, xi_prio: usize ) -> Result< String, &'static str >
+	fn positions( text: &str, width: usize ) -> Layout
 	{
-		let c    = hyphenation_crate::load( Language::English_US ).unwrap();
-		let hyph = Box::new( Hyphenator{ priority: hyph_prio, corpus: c, glue: "-\n".to_string() } );
-		let xi   = Box::new( Xi{ priority: xi_prio } );
+		let wrapper = Builder::new( width ).hyphenation( Language::English_US ).generator( Xi{ priority: 1 } ).break_word( true ).build().unwrap();
+		let layout  = wrapper.layout( text ).unwrap();
 
-		let reverse = try!( Wrapper::new( width, vec![ xi.clone(), hyph.clone() ], Vec::new(), UnicodeWidth, false ) );
-		let wrapper = try!( Wrapper::new( width, vec![ hyph      , xi           ], Vec::new(), UnicodeWidth, false ) );
+		// Every offset in the text maps back to itself, unless it's consumed or within a grapheme cluster.
+		//
+		for offset in 0..text.len() + 1
+		{
+			let ( line, column ) = layout.position( ByteOffset( offset ) ).unwrap();
 
-		let normal   = wrapper.wrap( string );
-		let reversed = reverse.wrap( string );
+			assert!( layout.offset( line, column ).unwrap().0 <= offset );
+		}
 
-		assert_eq!( normal, reversed );
+		layout
+	}
 
-		normal
+
+	#[test]
+	fn position()
+	{
+		let l = positions( "世界 the  hyphenation", 8 );
+
+		assert_eq!( l.text(), "世界 the\nhyphen-\nation" );
+
+		assert_eq!( l.position( ByteOffset(  0 ) ), Ok(( 0, WidthOffset( 0 ) )) );
+		assert_eq!( l.position( ByteOffset(  1 ) ), Ok(( 0, WidthOffset( 0 ) )) ); // within 世
+		assert_eq!( l.position( ByteOffset(  3 ) ), Ok(( 0, WidthOffset( 2 ) )) );
+		assert_eq!( l.position( ByteOffset( 10 ) ), Ok(( 0, WidthOffset( 8 ) )) ); // the consumed spaces
+		assert_eq!( l.position( ByteOffset( 11 ) ), Ok(( 0, WidthOffset( 8 ) )) );
+		assert_eq!( l.position( ByteOffset( 12 ) ), Ok(( 1, WidthOffset( 0 ) )) );
+		assert_eq!( l.position( ByteOffset( 18 ) ), Ok(( 2, WidthOffset( 0 ) )) ); // hyphenation cut
+		assert_eq!( l.position( ByteOffset( 23 ) ), Ok(( 2, WidthOffset( 5 ) )) );
+		assert!   ( l.position( ByteOffset( 24 ) ).is_err() );
+
+		assert_eq!( l.offset( 0, WidthOffset( 1 ) ), Ok( ByteOffset(  0 ) ) ); // within 世
+		assert_eq!( l.offset( 1, WidthOffset( 6 ) ), Ok( ByteOffset( 18 ) ) ); // the glue
+		assert_eq!( l.offset( 1, WidthOffset( 9 ) ), Ok( ByteOffset( 18 ) ) );
+		assert_eq!( l.offset( 2, WidthOffset( 3 ) ), Ok( ByteOffset( 21 ) ) );
+		assert!   ( l.offset( 3, WidthOffset( 0 ) ).is_err() );
 	}
 
 
 	#[test]
-	fn combine_generators_basic()
+	fn position_soft_hyphen()
 	{
-		assert_eq!( combine( "hyphenation is key", 7, 0, 0 ), Ok( "hyphen-\nation\nis key".to_string() ) );
+		let l = positions( "co\u{AD}ca co\u{AD}ca\u{200B}la foo", 8 );
+
+		assert_eq!( l.position( ByteOffset( 2 ) ), Ok(( 0, WidthOffset( 2 ) )) );
+		assert_eq!( l.offset  ( 0, WidthOffset( 2 ) ), Ok( ByteOffset( 2 ) ) );
 	}
 
+
 	#[test]
-	fn combine_priority()
+	fn position_in_unbroken()
 	{
-		assert_eq!( combine( "the hyphenation is key", 7, 0, 0 ), Ok( "the hy-\nphen-\nation\nis key".to_string() ) );
-		assert_eq!( combine( "the hyphenation is key", 7, 0, 3 ), Ok( "the hy-\nphen-\nation\nis key".to_string() ) );
-		assert_eq!( combine( "the hyphenation is key", 7, 0, 4 ), Ok( "the\nhyphen-\nation\nis key".to_string() ) );
+		let xi      = Box::new( Xi{ priority: 0 } );
+		let french  = Box::new( filter::french::French{ narrow_nbsp: true } );
+		let wrapper = Wrapper::new( 20, vec![ xi ], vec![ french ], UnicodeWidth, false ).unwrap();
+		let l       = wrapper.layout( "oui  !" ).unwrap();
+
+		assert_eq!( l.text(), "oui\u{202F}!" );
+
+		assert_eq!( l.position( ByteOffset( 4 ) ), Ok(( 0, WidthOffset( 3 ) )) );
+		assert_eq!( l.position( ByteOffset( 5 ) ), Ok(( 0, WidthOffset( 4 ) )) );
+		assert_eq!( l.offset  ( 0, WidthOffset( 4 ) ), Ok( ByteOffset( 5 ) ) );
+		assert_eq!( l.lines()[ 0 ].width(), WidthOffset( 5 ) );
 	}
 
+
 // 	#[test]
 // 	fn multiline_to_wrapline()
 // 	{
@@ -540,11 +2615,11 @@ mod tests
 	fn combine_filter( string: &str, width: usize, hyph_prio: usize, xi_prio: usize ) -> Result< String, &'static str >
 	{
 		let c       = hyphenation_crate::load( Language::English_US ).unwrap();
-		let hyph    = Box::new( Hyphenator{ priority: hyph_prio, corpus: c, glue: "-\n".to_string() } );
+		let hyph    = Box::new( Hyphenator{ priority: hyph_prio, corpus: std::sync::Arc::new( c ), glue: "-\n".to_string(), quality: Default::default() } );
 
 		let xi      = Box::new( Xi{ priority: xi_prio } );
 
-		let french  = Box::new( filter::french::French );
+		let french  = Box::new( filter::french::French::default() );
 
 		let wrapper = try!( Wrapper::new( width, vec![ hyph.clone(), xi.clone() ], vec![ french.clone() ], UnicodeWidth, false ) );
 		let reverse = try!( Wrapper::new( width, vec![ xi          , hyph       ], vec![ french         ], UnicodeWidth, false ) );
@@ -565,13 +2640,28 @@ mod tests
 	}
 
 
+	#[test]
+	fn french_narrow_nbsp()
+	{
+		let french  = Box::new( filter::french::French{ narrow_nbsp: true } );
+		let wrapper = Wrapper::new( 9, vec![ Box::new( Xi{ priority: 0 } ) ], vec![ french ], UnicodeWidth, false ).unwrap();
+
+		assert_eq!( wrapper.wrap( "« oui » !"        ), Ok(         "«\u{202F}oui\u{202F}»\u{202F}!".to_string() ) );
+		assert_eq!( wrapper.wrap( "il dit « oui » !" ), Ok( "il dit\n«\u{202F}oui\u{202F}»\u{202F}!".to_string() ) );
+
+		// The spaces take the room of the narrow no-break space that replaces them.
+		//
+		assert_eq!( wrapper.wrap( "oui    ! non"     ), Ok(         "oui\u{202F}! non".to_string()                ) );
+	}
+
+
 	//----------------------
 	// break_word Generators
 	//
 	fn breaks( string: &str, width: usize, hyph_prio: usize, xi_prio: usize ) -> Result< String, &'static str >
 	{
 		let c    = hyphenation_crate::load( Language::English_US ).unwrap();
-		let hyph = Box::new( Hyphenator{ priority: hyph_prio, corpus: c, glue: "-\n".to_string() } );
+		let hyph = Box::new( Hyphenator{ priority: hyph_prio, corpus: std::sync::Arc::new( c ), glue: "-\n".to_string(), quality: Default::default() } );
 		let xi   = Box::new( Xi{ priority: xi_prio } );
 
 		let wrapper = try!( Wrapper::new( width, vec![ hyph, xi ], Vec::new(), UnicodeWidth, true ) );
@@ -610,6 +2700,68 @@ mod tests
 	}
 
 
+	// The last split fits on a line that starts right at it, which must not make us choose it again.
+	//
+	#[test]
+	fn break_after_last_split()
+	{
+		let wrapper = Builder::new( 5 ).hyphenation( Language::English_US ).default_generator( false ).break_word( true ).build().unwrap();
+
+		assert_eq!( wrapper.wrap( "hyphenation xx" ), Ok( "hy-\nphen-\nation\n xx".to_string() ) );
+	}
+
+
+
+	//--------
+	// Threads
+	//
+	fn shareable< T: Send + Sync >( _: &T ) {}
+
+
+	#[test]
+	fn threads()
+	{
+		let text    = "the hyphenation of « garbage collection » is key, e.g. for 10 km of text";
+		let wrapper = std::sync::Arc::new( Builder::english( 12 ).build().unwrap() );
+
+		shareable( &*wrapper );
+
+		let expected = wrapper.wrap( text );
+
+		let threads: Vec<_> = ( 0..4 ).map( |_|
+		{
+			let wrapper = wrapper.clone();
+
+			std::thread::spawn( move || wrapper.wrap( text ) )
+
+		}).collect();
+
+		for thread in threads
+		{
+			assert_eq!( thread.join().unwrap(), expected );
+		}
+
+		assert!( expected.is_ok() );
+	}
+
+
+	#[cfg(feature = "rayon")]
+	#[test]
+	fn wrap_paragraphs()
+	{
+		let wrapper    = Builder::english( 12 ).build().unwrap();
+		let paragraphs = ( 0..1000 ).map( |i| format!( "paragraph {} on hyphenation and garbage collection", i ) ).collect::< Vec<_> >();
+		let expected   = paragraphs.iter().map( |p| wrapper.wrap( p ) ).collect::< Vec<_> >();
+
+		assert_eq!( wrapper.wrap_paragraphs( &paragraphs ), expected );
+
+
+		let words = Builder::new( 5 ).build().unwrap();
+
+		assert_eq!( words.wrap_paragraphs( &[ "foo bar", "foobarbaz", "" ] ), vec![ Ok( "foo\nbar".to_string() ), Err( "No valid split point found" ), Ok( String::new() ) ] );
+	}
+
+
 
 	// fn lorem_ipsum(length: usize) -> &'static str {
 	//     let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. Maecenas feugiat non mi \